@@ -1,46 +1,63 @@
-use base64::{engine::general_purpose, Engine as _};
-use hmac::{Hmac, Mac};
+use crate::auth::Credentials;
+use crate::clock::SharedClock;
+use crate::fees::Fees;
+use crate::metrics::METRICS;
 use reqwest::header;
 use serde_urlencoded;
-use sha2::{Digest, Sha256, Sha512};
 use std::collections::HashMap;
+use std::str;
 use std::sync::Arc;
-use std::{str, time};
+use std::time::Instant;
 use tokio::sync::Mutex;
-
-const BASE_URL: &str = "https://api.kraken.com";
+use tracing::{debug, info, warn};
 
 pub struct Portfolio {
     assets: HashMap<String, (f64, f64)>, // (amount, price)
-    pub signer: Arc<Mutex<Signer>>,
+    fees: HashMap<String, Fees>,         // By pair
+    reserved_cash: HashMap<String, f64>, // USD committed to live bids, by pair
+    updated: u64,                        // When an amount or price last changed
+    clock: SharedClock,
 }
 
 impl Portfolio {
-    pub async fn new(signer: Arc<Mutex<Signer>>) -> Self {
-        println!("Initializing portfolio...");
+    pub async fn new(signer: Arc<Mutex<Signer>>, clock: SharedClock) -> Self {
+        info!("Initializing portfolio...");
         let balances = { signer.lock().await.get_account_balances().await };
         let mut assets = HashMap::new();
         for (asset, balance) in balances.as_object().unwrap() {
             let amount = balance.as_str().unwrap().parse::<f64>().unwrap();
             let price = if asset == "ZUSD" { 1.0 } else { 0.0 };
-            println!("Found {}: {} @ {}", asset, amount, price);
+            debug!(asset, amount, price, "Found balance");
             if amount == 0.0 {
                 continue;
             }
             if let Some(stripped) = asset.strip_prefix("X") {
-                println!("Inserting: {}", stripped);
+                info!(asset = stripped, amount, "Inserting");
                 assets.insert(stripped.to_string(), (amount, price));
             } else {
-                println!("Inserting: {}", asset);
+                info!(asset, amount, "Inserting");
                 assets.insert(asset.clone(), (amount, price));
             }
         }
+        Portfolio::from_assets(assets, clock)
+    }
+
+    /// Returns a portfolio holding the given (amount, price) per asset.
+    pub fn from_assets(assets: HashMap<String, (f64, f64)>, clock: SharedClock) -> Self {
         Portfolio {
-            assets: assets,
-            signer: signer,
+            assets,
+            fees: HashMap::new(),
+            reserved_cash: HashMap::new(),
+            updated: clock.now_secs(),
+            clock,
         }
     }
 
+    /// Returns when an amount or price last changed.
+    pub fn get_updated(&self) -> u64 {
+        self.updated
+    }
+
     /// Returns a tuple of the amount and price of the asset.
     pub fn get_pair(&self, pair: String) -> (f64, f64) {
         let asset = if let Some(stripped) = pair.strip_suffix("/USD") {
@@ -75,12 +92,18 @@ impl Portfolio {
         (target - amount * price / total_value) / target * 100.0
     }
 
-    fn get_asset_allocation(&self, asset: String) -> f64 {
+    /// Returns true once every asset has a price.
+    pub fn has_prices(&self) -> bool {
+        self.assets.values().all(|(_, price)| *price != 0.0)
+    }
+
+    /// Returns the share of the total value held in the asset.
+    pub fn get_asset_allocation(&self, asset: String) -> f64 {
         let (amount, price) = self.get_pair(asset);
         amount * price / self.get_total_value()
     }
 
-    fn get_total_value(&self) -> f64 {
+    pub fn get_total_value(&self) -> f64 {
         let mut total = 0.0;
         for (amount, price) in self.assets.values() {
             total += amount * price;
@@ -94,19 +117,76 @@ impl Portfolio {
         } else {
             pair
         };
-        println!("Update asset: {}", asset);
+        debug!(asset, order_vol, order_price, "Update asset");
+        self.updated = self.clock.now_secs();
         // Update token
         if let Some((amount, price)) = self.assets.get_mut(&asset) {
-            *amount = *amount + order_vol;
+            *amount += order_vol;
             *price = order_price;
         } else {
-            println!("Asset not found: {}", asset);
+            warn!(asset, "Asset not found");
         }
         // Update USD
         if let Some((amount, _)) = self.assets.get_mut("ZUSD") {
-            *amount = *amount - (order_vol * order_price);
+            *amount -= order_vol * order_price;
         } else {
-            println!("Asset not found: ZUSD");
+            warn!(asset = "ZUSD", "Asset not found");
+        }
+    }
+
+    /// Deducts a fee charged in USD.
+    pub fn charge_fee(&mut self, fee: f64) {
+        if let Some((amount, _)) = self.assets.get_mut("ZUSD") {
+            *amount -= fee;
+            self.updated = self.clock.now_secs();
+        }
+    }
+
+    /// Returns the pair's fee rates, or the defaults if they haven't been fetched yet.
+    pub fn get_fees(&self, pair: &str) -> Fees {
+        self.fees.get(pair).copied().unwrap_or_default()
+    }
+
+    pub fn set_fees(&mut self, pair: String, fees: Fees) {
+        self.fees.insert(pair, fees);
+    }
+
+    /// Records the USD committed to the pair's live bids.
+    pub fn set_reserved_cash(&mut self, pair: String, reserved: f64) {
+        self.reserved_cash.insert(pair, reserved);
+    }
+
+    /// Returns the USD committed to live bids on every pair but `pair`.
+    pub fn get_reserved_cash_except(&self, pair: &str) -> f64 {
+        self.reserved_cash
+            .iter()
+            .filter(|(other, _)| *other != pair)
+            .map(|(_, reserved)| reserved)
+            .sum()
+    }
+
+    /// Returns the (amount, price) of every asset.
+    pub fn get_assets(&self) -> HashMap<String, (f64, f64)> {
+        self.assets.clone()
+    }
+
+    /// Restores prices from a journaled snapshot. Amounts fetched from the exchange take
+    /// precedence, mismatches are only reported.
+    pub fn restore(&mut self, journaled: &HashMap<String, (f64, f64)>) {
+        for (asset, (amount, price)) in self.assets.iter_mut() {
+            if let Some((journaled_amount, journaled_price)) = journaled.get(asset) {
+                if *price == 0.0 {
+                    *price = *journaled_price;
+                }
+                if (*amount - journaled_amount).abs() > 1e-9 {
+                    warn!(
+                        asset,
+                        journaled = journaled_amount,
+                        exchange = *amount,
+                        "Journaled balance differs from exchange"
+                    );
+                }
+            }
         }
     }
 
@@ -114,40 +194,36 @@ impl Portfolio {
         if let Some(stripped) = pair.strip_suffix("/USD") {
             if let Some((_, price)) = self.assets.get_mut(stripped) {
                 *price = new_price;
+                self.updated = self.clock.now_secs();
             } else {
-                println!("Asset not found for {}", pair);
+                warn!(pair, "Asset not found");
             };
         } else {
-            println!("Not /USD pair: {}", pair);
+            warn!(pair, "Not /USD pair");
         };
     }
 }
 
 /// Signer for Kraken API. Handles signing and sending requests.
 pub struct Signer {
-    key: String,
-    secret: String,
-    secret_slice: [u8; 64],
+    credentials: Credentials,
     client: reqwest::Client,
+    base_url: String,
+    clock: SharedClock,
 }
 
 impl Signer {
-    pub async fn new(key: String, secret: String) -> Self {
+    pub async fn new(credentials: Credentials, base_url: String, clock: SharedClock) -> Self {
         Signer {
-            key: key.clone(),
-            secret: secret.clone(),
-            secret_slice: general_purpose::STANDARD
-                .decode(secret.as_str())
-                .unwrap()
-                .as_slice()
-                .try_into()
-                .unwrap(),
+            credentials,
             client: reqwest::Client::new(),
+            base_url,
+            clock,
         }
     }
 
     fn get_nonce(&self) -> String {
-        time::UNIX_EPOCH.elapsed().unwrap().as_millis().to_string()
+        self.clock.now_millis().to_string()
     }
 
     /// Returns a tuple of the signed data and the signature.
@@ -160,36 +236,26 @@ impl Signer {
         let nonce = self.get_nonce();
         let mut data_stamped = data;
         data_stamped.push(("nonce", &nonce));
+        if let Some(otp) = self.credentials.otp() {
+            data_stamped.push(("otp", otp));
+        }
         let post_data = serde_urlencoded::to_string(&data_stamped).unwrap();
-
-        let mut hasher = Sha256::new();
-        hasher.update(nonce.as_bytes());
-        hasher.update(post_data.as_bytes());
-        let encoded_payload = &hasher.finalize();
-
-        let mut mac =
-            Hmac::<Sha512>::new_from_slice(&self.secret_slice).expect("Couldn't create HMAC");
-        mac.update(&url.as_bytes());
-        mac.update(&encoded_payload);
-        let signature = mac.finalize().into_bytes();
-
-        let sign = general_purpose::STANDARD.encode(signature);
+        let sign = self.credentials.sign(url, &nonce, &post_data);
 
         (post_data, sign)
     }
 
-    /// Returns the ws auth token.
-    pub async fn get_ws_token(&self) -> String {
-        const PATH: &str = "/0/private/GetWebSocketsToken";
-
-        let (post_data, sign) = self.sign(PATH, vec![]);
+    /// Signs and posts a request to a private endpoint, returning the parsed response.
+    async fn post(&self, path: &str, data: Vec<(&str, &str)>) -> serde_json::Value {
+        let (post_data, sign) = self.sign(path, data);
         let mut headers = header::HeaderMap::new();
-        headers.insert("API-Key", self.key.as_str().parse().unwrap());
+        headers.insert("API-Key", self.credentials.key().parse().unwrap());
         headers.insert("API-Sign", sign.as_str().parse().unwrap());
 
+        let start = Instant::now();
         let response = self
             .client
-            .post(format!("{}{}", BASE_URL, PATH).as_str())
+            .post(format!("{}{}", self.base_url, path).as_str())
             .headers(headers)
             .body(post_data)
             .send()
@@ -197,31 +263,61 @@ impl Signer {
             .unwrap();
 
         let body = response.text().await.unwrap();
+        METRICS.observe_rest(path, start);
 
-        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        serde_json::from_str(&body).unwrap()
+    }
+
+    /// Returns the ws auth token.
+    pub async fn get_ws_token(&self) -> String {
+        let json = self.post("/0/private/GetWebSocketsToken", vec![]).await;
         json["result"]["token"].as_str().unwrap().to_string()
     }
 
     pub async fn get_account_balances(&self) -> serde_json::Value {
-        const PATH: &str = "/0/private/Balance";
-
-        let (post_data, sign) = self.sign(PATH, vec![]);
-        let mut headers = header::HeaderMap::new();
-        headers.insert("API-Key", self.key.as_str().parse().unwrap());
-        headers.insert("API-Sign", sign.as_str().parse().unwrap());
+        let json = self.post("/0/private/Balance", vec![]).await;
+        json["result"].clone()
+    }
 
-        let response = self
-            .client
-            .post(format!("{}{}", BASE_URL, PATH).as_str())
-            .headers(headers)
-            .body(post_data)
-            .send()
-            .await
-            .unwrap();
+    /// Returns the account's open orders keyed by txid.
+    pub async fn get_open_orders(&self) -> serde_json::Value {
+        let json = self.post("/0/private/OpenOrders", vec![]).await;
+        json["result"]["open"].clone()
+    }
 
-        let body = response.text().await.unwrap();
+    /// Cancels every open order on the account, returning how many were cancelled.
+    pub async fn cancel_all_orders(&self) -> Option<u64> {
+        let json = self.post("/0/private/CancelAll", vec![]).await;
+        let count = json["result"]["count"].as_u64();
+        if count.is_none() {
+            warn!(error = %json["error"], "Failed to cancel all orders");
+        }
+        count
+    }
 
-        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
-        json["result"].clone()
+    /// Returns the pair's current fee rates for the account's 30 day volume tier.
+    pub async fn get_trade_fees(&self, pair: &str) -> Option<Fees> {
+        let pair = pair.replace('/', "");
+        let json = self
+            .post(
+                "/0/private/TradeVolume",
+                vec![("pair", &pair), ("fee-info", "true")],
+            )
+            .await;
+        // Fees are keyed by Kraken's name for the pair and given in percentage
+        let fee = |schedule: &str| {
+            json["result"][schedule].as_object()?.values().next()?["fee"]
+                .as_str()?
+                .parse::<f64>()
+                .ok()
+                .map(|fee| fee / 100.0)
+        };
+        match (fee("fees_maker"), fee("fees")) {
+            (Some(maker), Some(taker)) => Some(Fees { maker, taker }),
+            _ => {
+                warn!(pair, error = %json["error"], "Failed to get trade fees");
+                None
+            }
+        }
     }
 }
@@ -0,0 +1,365 @@
+use crate::account::Portfolio;
+use crate::control::{Controls, Markets};
+use crate::journal::{Event, Journal};
+use crate::metrics::METRICS;
+use crate::product::Market;
+use axum::extract::{ConnectInfo, Path, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn, Instrument};
+use zeroize::Zeroizing;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub portfolio: Arc<Mutex<Portfolio>>,
+    pub markets: Markets,
+    pub controls: Arc<Mutex<Controls>>,
+    pub journal: Arc<Mutex<Journal>>,
+    pub pairs: Vec<String>, // Configured pairs
+    /// Bearer token required by the control routes, which are refused when it isn't set.
+    pub token: Option<Arc<Zeroizing<String>>>,
+}
+
+#[derive(Serialize)]
+struct AssetStatus {
+    amount: f64,
+    price: f64,
+    value: f64,
+    weight: f64,
+    target_delta: f64, // In percentage
+}
+
+/// Serves the status and control API until the listener fails. The listener is bound by the
+/// caller, so a port that is taken fails startup instead of this task.
+pub async fn serve(state: AppState, listener: TcpListener) {
+    if state.token.is_none() {
+        warn!("No API_TOKEN set, control routes are disabled");
+    }
+    info!(addr = %listener.local_addr().unwrap(), "API listening");
+    let app = router(state).into_make_service_with_connect_info::<SocketAddr>();
+    if let Err(e) = axum::serve(listener, app).await {
+        error!(error = %e, "API stopped");
+    }
+}
+
+/// Returns the API routes. Status routes are open, control routes need the bearer token.
+pub fn router(state: AppState) -> Router {
+    let control = Router::new()
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/cancel-all", post(cancel_all))
+        .route("/rebalance", post(rebalance))
+        .route("/kill-switch/reset", post(reset_kill_switch))
+        .route("/pairs/:pair/pause", post(pause_pair))
+        .route("/pairs/:pair/resume", post(resume_pair))
+        .route("/pairs/:pair/cancel-all", post(cancel_all_pair))
+        .route("/pairs/:pair/rebalance", post(rebalance_pair))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize));
+
+    Router::new()
+        .route("/health", get(health))
+        .route("/ready", get(ready))
+        .route("/metrics", get(metrics))
+        .route("/portfolio", get(portfolio))
+        .route("/orders", get(orders))
+        .route("/pnl", get(pnl))
+        .route("/controls", get(controls))
+        .merge(control)
+        .with_state(state)
+}
+
+/// Rejects requests without `Authorization: Bearer <API_TOKEN>`.
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let given = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match (&state.token, given) {
+        (Some(token), Some(given)) if constant_time_eq(token.as_bytes(), given.as_bytes()) => {
+            next.run(request).await
+        }
+        _ => {
+            warn!(path = %request.uri().path(), "API: unauthorized control request");
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+/// Compares without returning early, so the time taken doesn't reveal the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+/// Ready once every configured pair has a connected market with a price.
+async fn ready(State(state): State<AppState>) -> impl IntoResponse {
+    let markets = get_markets(&state).await;
+    let mut not_ready = Vec::new();
+    for pair in state.pairs.iter() {
+        let ready = match markets.iter().find(|m| m.0 == *pair) {
+            Some((_, market)) => market.lock().await.is_ready(),
+            None => false,
+        };
+        if !ready {
+            not_ready.push(pair.clone());
+        }
+    }
+
+    if not_ready.is_empty() {
+        (StatusCode::OK, Json(json!({ "ready": true })))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "ready": false, "not_ready": not_ready })),
+        )
+    }
+}
+
+/// Refreshes the portfolio and order gauges, then exports all metrics.
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    {
+        let portfolio = state.portfolio.lock().await;
+        METRICS.portfolio_value.set(portfolio.get_total_value());
+        for (asset, (amount, price)) in portfolio.get_assets() {
+            let labels = [asset.as_str()];
+            METRICS
+                .asset_value
+                .with_label_values(&labels)
+                .set(amount * price);
+            METRICS
+                .asset_weight
+                .with_label_values(&labels)
+                .set(portfolio.get_asset_allocation(asset.clone()));
+            METRICS
+                .asset_target_delta
+                .with_label_values(&labels)
+                .set(portfolio.get_pair_target_delta(asset.clone()));
+        }
+    }
+
+    for (pair, market) in get_markets(&state).await {
+        let orders = market.lock().await.get_orders();
+        for side in ["buy", "sell"] {
+            let count = orders.iter().filter(|o| o.side == side).count();
+            METRICS
+                .open_orders
+                .with_label_values(&[&pair, side])
+                .set(count as i64);
+        }
+    }
+
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4",
+        )],
+        METRICS.encode(),
+    )
+}
+
+async fn portfolio(State(state): State<AppState>) -> impl IntoResponse {
+    let portfolio = state.portfolio.lock().await;
+    let total_value = portfolio.get_total_value();
+    let mut assets = HashMap::new();
+    for (asset, (amount, price)) in portfolio.get_assets() {
+        assets.insert(
+            asset.clone(),
+            AssetStatus {
+                amount,
+                price,
+                value: amount * price,
+                weight: portfolio.get_asset_allocation(asset.clone()),
+                target_delta: portfolio.get_pair_target_delta(asset),
+            },
+        );
+    }
+    Json(json!({
+        "total_value": total_value,
+        "updated": portfolio.get_updated(),
+        "assets": assets,
+    }))
+}
+
+async fn orders(State(state): State<AppState>) -> impl IntoResponse {
+    let mut orders = HashMap::new();
+    for (pair, market) in get_markets(&state).await {
+        orders.insert(pair, market.lock().await.get_orders());
+    }
+    Json(orders)
+}
+
+/// Realized and unrealized profit per asset, and against holding the starting portfolio.
+async fn pnl(State(state): State<AppState>) -> impl IntoResponse {
+    let assets = { state.portfolio.lock().await.get_assets() };
+    Json(state.journal.lock().await.get_pnl().report(&assets))
+}
+
+async fn controls(State(state): State<AppState>) -> impl IntoResponse {
+    let mut unhealthy_pairs = HashMap::new();
+    for (pair, market) in get_markets(&state).await {
+        if let Some(reason) = market.lock().await.get_unhealthy() {
+            unhealthy_pairs.insert(pair, reason.to_string());
+        }
+    }
+    let controls = state.controls.lock().await;
+    Json(json!({
+        "paused": controls.is_paused_globally(),
+        "paused_pairs": controls.get_paused_pairs(),
+        "unhealthy_pairs": unhealthy_pairs,
+        "kill_switch": controls.kill_switch.get_tripped(),
+    }))
+}
+
+async fn pause(State(state): State<AppState>) -> impl IntoResponse {
+    info!("API: pausing all pairs");
+    state.controls.lock().await.pause();
+    for (_, market) in get_markets(&state).await {
+        let mut market = market.lock().await;
+        let span = market.span();
+        market.cancel_all_orders().instrument(span).await;
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn resume(State(state): State<AppState>) -> impl IntoResponse {
+    info!("API: resuming all pairs");
+    state.controls.lock().await.resume();
+    StatusCode::NO_CONTENT
+}
+
+/// Re-enables trading after the kill switch tripped. The operator named in the optional
+/// X-Operator header and the client address are logged and journaled.
+async fn reset_kill_switch(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let mut controls = state.controls.lock().await;
+    let reason = match controls.kill_switch.get_tripped() {
+        Some(reason) => reason.to_string(),
+        None => return StatusCode::CONFLICT,
+    };
+    let operator = headers
+        .get("x-operator")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown");
+    let by = format!("{} from {}", operator, addr);
+    warn!(%by, %reason, "API: resetting kill switch");
+    controls.kill_switch.reset();
+    state
+        .journal
+        .lock()
+        .await
+        .append(Event::KillSwitchReset { by });
+    StatusCode::NO_CONTENT
+}
+
+async fn cancel_all(State(state): State<AppState>) -> impl IntoResponse {
+    info!("API: cancelling all orders");
+    for (_, market) in get_markets(&state).await {
+        let mut market = market.lock().await;
+        let span = market.span();
+        market.cancel_all_orders().instrument(span).await;
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn rebalance(State(state): State<AppState>) -> impl IntoResponse {
+    info!("API: rebalancing all pairs");
+    for (_, market) in get_markets(&state).await {
+        let mut market = market.lock().await;
+        let span = market.span();
+        market.rebalance().instrument(span).await;
+    }
+    StatusCode::NO_CONTENT
+}
+
+async fn pause_pair(State(state): State<AppState>, Path(pair): Path<String>) -> impl IntoResponse {
+    let (pair, market) = match find_market(&state, &pair).await {
+        Some(found) => found,
+        None => return StatusCode::NOT_FOUND,
+    };
+    info!(%pair, "API: pausing");
+    state.controls.lock().await.pause_pair(&pair);
+    let mut market = market.lock().await;
+    let span = market.span();
+    market.cancel_all_orders().instrument(span).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn resume_pair(State(state): State<AppState>, Path(pair): Path<String>) -> impl IntoResponse {
+    let (pair, _) = match find_market(&state, &pair).await {
+        Some(found) => found,
+        None => return StatusCode::NOT_FOUND,
+    };
+    info!(%pair, "API: resuming");
+    state.controls.lock().await.resume_pair(&pair);
+    StatusCode::NO_CONTENT
+}
+
+async fn cancel_all_pair(
+    State(state): State<AppState>,
+    Path(pair): Path<String>,
+) -> impl IntoResponse {
+    let (pair, market) = match find_market(&state, &pair).await {
+        Some(found) => found,
+        None => return StatusCode::NOT_FOUND,
+    };
+    info!(%pair, "API: cancelling all orders for");
+    let mut market = market.lock().await;
+    let span = market.span();
+    market.cancel_all_orders().instrument(span).await;
+    StatusCode::NO_CONTENT
+}
+
+async fn rebalance_pair(
+    State(state): State<AppState>,
+    Path(pair): Path<String>,
+) -> impl IntoResponse {
+    let (pair, market) = match find_market(&state, &pair).await {
+        Some(found) => found,
+        None => return StatusCode::NOT_FOUND,
+    };
+    info!(%pair, "API: rebalancing");
+    let mut market = market.lock().await;
+    let span = market.span();
+    market.rebalance().instrument(span).await;
+    StatusCode::NO_CONTENT
+}
+
+/// Copies the registered markets so no lock is held while talking to them.
+async fn get_markets(state: &AppState) -> Vec<(String, Arc<Mutex<Market>>)> {
+    let markets = state.markets.lock().await;
+    markets
+        .iter()
+        .map(|(pair, market)| (pair.clone(), market.clone()))
+        .collect()
+}
+
+/// Finds a market by pair. Since pairs contain a slash, "XBT-USD", "XBTUSD" and the URL
+/// encoded "XBT%2FUSD" are all accepted.
+async fn find_market(state: &AppState, pair: &str) -> Option<(String, Arc<Mutex<Market>>)> {
+    let wanted = normalize_pair(pair);
+    get_markets(state)
+        .await
+        .into_iter()
+        .find(|(pair, _)| normalize_pair(pair) == wanted)
+}
+
+fn normalize_pair(pair: &str) -> String {
+    pair.replace(['/', '-'], "").to_uppercase()
+}
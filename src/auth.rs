@@ -0,0 +1,172 @@
+use crate::keystore;
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use std::error::Error;
+use std::{env, fmt, fs};
+use zeroize::Zeroizing;
+
+const SECRET_LENGTH: usize = 64; // bytes, once decoded
+
+/// A Kraken API key pair, validated when loaded. The secret is wiped from memory on drop and
+/// never printed.
+#[derive(Clone)]
+pub struct Credentials {
+    key: Zeroizing<String>,
+    secret: Zeroizing<[u8; SECRET_LENGTH]>,
+    otp: Option<Zeroizing<String>>, // Two-factor password, if the key requires one
+}
+
+/// Which API key to load. Balances and fees only need a query key, while the WebSocket token
+/// used for orders needs one with trading permissions.
+#[derive(Debug, Clone, Copy)]
+pub enum Role {
+    Trading,
+    ReadOnly,
+}
+
+#[derive(Debug)]
+pub enum CredentialsError {
+    InvalidKey,
+    InvalidSecret,
+    SecretLength(usize),
+    Missing(String),
+    Unreadable(String, std::io::Error),
+    Keystore(String),
+}
+
+impl fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialsError::InvalidKey => write!(f, "API key is empty or contains whitespace"),
+            CredentialsError::InvalidSecret => write!(f, "API secret is not base64"),
+            CredentialsError::SecretLength(length) => write!(
+                f,
+                "API secret is {} bytes, expected {}",
+                length, SECRET_LENGTH
+            ),
+            CredentialsError::Missing(name) => write!(f, "{} not set", name),
+            CredentialsError::Unreadable(path, e) => write!(f, "Failed to read {}: {}", path, e),
+            CredentialsError::Keystore(e) => write!(f, "Keystore: {}", e),
+        }
+    }
+}
+
+impl Error for CredentialsError {}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("key", &"[redacted]")
+            .field("secret", &"[redacted]")
+            .field("otp", &self.otp.as_ref().map(|_| "[redacted]"))
+            .finish()
+    }
+}
+
+impl Role {
+    /// Prefix of the environment variables holding this role's credentials.
+    fn prefix(&self) -> &'static str {
+        match self {
+            Role::Trading => "KRAKEN",
+            Role::ReadOnly => "KRAKEN_READ",
+        }
+    }
+}
+
+impl Credentials {
+    /// Validates the key and decodes the base64 secret.
+    pub fn new(key: &str, secret: &str, otp: Option<String>) -> Result<Self, CredentialsError> {
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(CredentialsError::InvalidKey);
+        }
+        let decoded = Zeroizing::new(
+            general_purpose::STANDARD
+                .decode(secret.trim())
+                .map_err(|_| CredentialsError::InvalidSecret)?,
+        );
+        let secret = decoded
+            .as_slice()
+            .try_into()
+            .map_err(|_| CredentialsError::SecretLength(decoded.len()))?;
+        Ok(Credentials {
+            key: Zeroizing::new(key.to_string()),
+            secret: Zeroizing::new(secret),
+            otp: otp.map(Zeroizing::new),
+        })
+    }
+
+    /// Loads the role's credentials from the environment, returning `None` if none are set.
+    ///
+    /// With the `KRAKEN` prefix for trading and `KRAKEN_READ` for read-only keys, they are
+    /// read from `<prefix>_KEYSTORE`, an encrypted keystore unlocked with `KEYSTORE_PASSPHRASE`
+    /// or a passphrase typed at the terminal. Otherwise from `<prefix>_KEY` and
+    /// `<prefix>_SECRET`, each of which may instead be given as a path in `<prefix>_KEY_FILE`
+    /// or `<prefix>_SECRET_FILE` for mounted secrets. `<prefix>_OTP` is the optional 2FA
+    /// password.
+    pub fn load(role: Role) -> Result<Option<Self>, CredentialsError> {
+        let prefix = role.prefix();
+        let otp = read_var(&format!("{}_OTP", prefix))?.map(|otp| otp.to_string());
+        if let Ok(path) = env::var(format!("{}_KEYSTORE", prefix)) {
+            let passphrase = match read_var("KEYSTORE_PASSPHRASE")? {
+                Some(passphrase) => passphrase,
+                None => Zeroizing::new(
+                    rpassword::prompt_password(format!("Passphrase for {}: ", path))
+                        .map_err(|e| CredentialsError::Unreadable(path.clone(), e))?,
+                ),
+            };
+            return keystore::open(&path, &passphrase, otp).map(Some);
+        }
+
+        let key_var = format!("{}_KEY", prefix);
+        let secret_var = format!("{}_SECRET", prefix);
+        match (read_var(&key_var)?, read_var(&secret_var)?) {
+            (Some(key), Some(secret)) => Credentials::new(&key, &secret, otp).map(Some),
+            (None, None) => Ok(None),
+            (Some(_), None) => Err(CredentialsError::Missing(secret_var)),
+            (None, Some(_)) => Err(CredentialsError::Missing(key_var)),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn otp(&self) -> Option<&str> {
+        self.otp.as_deref().map(String::as_str)
+    }
+
+    /// Returns the API-Sign header for a private request.
+    pub fn sign(&self, path: &str, nonce: &str, post_data: &str) -> String {
+        sign(self.secret.as_slice(), path, nonce, post_data)
+    }
+}
+
+/// Returns Kraken's signature: base64(HMAC-SHA512(secret, path + SHA256(nonce + post_data))).
+pub fn sign(secret: &[u8], path: &str, nonce: &str, post_data: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce.as_bytes());
+    hasher.update(post_data.as_bytes());
+
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(path.as_bytes());
+    mac.update(&hasher.finalize());
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+/// Reads `name` from the environment, or from the file named by `<name>_FILE`.
+pub fn read_var(name: &str) -> Result<Option<Zeroizing<String>>, CredentialsError> {
+    if let Ok(value) = env::var(name) {
+        return Ok(Some(Zeroizing::new(value)));
+    }
+    match env::var(format!("{}_FILE", name)) {
+        Ok(path) => {
+            let contents = Zeroizing::new(
+                fs::read_to_string(&path).map_err(|e| CredentialsError::Unreadable(path, e))?,
+            );
+            Ok(Some(Zeroizing::new(contents.trim().to_string())))
+        }
+        Err(_) => Ok(None),
+    }
+}
@@ -0,0 +1,187 @@
+use crate::messages::BookData;
+use crate::strategy::Side;
+use std::collections::BTreeMap;
+
+pub const BOOK_DEPTH: usize = 10;
+const CHECKSUM_LEVELS: usize = 10;
+
+#[derive(Clone, Debug)]
+struct Level {
+    price: f64,
+    volume: f64,
+    volume_str: String, // As received, needed for the checksum
+}
+
+/// Local L2 order book for a single pair, kept in sync with Kraken's `book` channel.
+///
+/// Levels are keyed by the price with the decimal point removed. Kraken sends prices with a
+/// fixed number of decimals per pair, so this orders the same as the price itself and is also
+/// the form the checksum expects.
+#[derive(Clone, Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<u64, Level>,
+    asks: BTreeMap<u64, Level>,
+    valid: bool,
+}
+
+#[derive(Debug)]
+pub struct ChecksumMismatch {
+    pub expected: u32,
+    pub actual: u32,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        OrderBook::default()
+    }
+
+    /// Returns true once a snapshot has been received and all checksums since matched.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Drops all levels until the next snapshot.
+    pub fn invalidate(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.valid = false;
+    }
+
+    /// Applies a snapshot or update and verifies the checksum if one was sent.
+    pub fn apply(&mut self, data: &BookData) -> Result<(), ChecksumMismatch> {
+        if data.snapshot_asks.is_some() || data.snapshot_bids.is_some() {
+            self.bids.clear();
+            self.asks.clear();
+            self.valid = true;
+        } else if !self.valid {
+            return Ok(()); // Wait for a snapshot
+        }
+
+        for entry in data.snapshot_asks.iter().chain(data.asks.iter()).flatten() {
+            OrderBook::update_level(&mut self.asks, entry);
+        }
+        for entry in data.snapshot_bids.iter().chain(data.bids.iter()).flatten() {
+            OrderBook::update_level(&mut self.bids, entry);
+        }
+
+        // Levels outside the subscribed depth are no longer updated, so drop them.
+        while self.asks.len() > BOOK_DEPTH {
+            self.asks.pop_last();
+        }
+        while self.bids.len() > BOOK_DEPTH {
+            self.bids.pop_first();
+        }
+
+        if let Some(checksum) = &data.checksum {
+            let expected = checksum.parse::<u32>().unwrap_or_default();
+            let actual = self.checksum();
+            if expected != actual {
+                self.invalidate();
+                return Err(ChecksumMismatch { expected, actual });
+            }
+        }
+        Ok(())
+    }
+
+    fn update_level(levels: &mut BTreeMap<u64, Level>, entry: &[String]) {
+        let key = match price_key(&entry[0]) {
+            Some(key) => key,
+            None => return,
+        };
+        let volume = entry[1].parse::<f64>().unwrap_or_default();
+        if volume == 0.0 {
+            levels.remove(&key);
+        } else {
+            levels.insert(
+                key,
+                Level {
+                    price: entry[0].parse::<f64>().unwrap(),
+                    volume,
+                    volume_str: entry[1].clone(),
+                },
+            );
+        }
+    }
+
+    /// CRC32 over the top asks (ascending) then top bids (descending), each level being the
+    /// price and volume with the decimal point and leading zeros removed.
+    pub fn checksum(&self) -> u32 {
+        let mut payload = String::new();
+        let asks = self.asks.iter().take(CHECKSUM_LEVELS);
+        let bids = self.bids.iter().rev().take(CHECKSUM_LEVELS);
+        for (key, level) in asks.chain(bids) {
+            payload.push_str(&key.to_string());
+            payload.push_str(strip_number(&level.volume_str).as_str());
+        }
+        crc32fast::hash(payload.as_bytes())
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.values().next_back().map(|l| (l.price, l.volume))
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.values().next().map(|l| (l.price, l.volume))
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        let (bid, _) = self.best_bid()?;
+        let (ask, _) = self.best_ask()?;
+        Some((bid + ask) / 2.0)
+    }
+
+    /// Top of book price weighted by the volume on the opposite side.
+    pub fn microprice(&self) -> Option<f64> {
+        let (bid, bid_vol) = self.best_bid()?;
+        let (ask, ask_vol) = self.best_ask()?;
+        Some((bid * ask_vol + ask * bid_vol) / (bid_vol + ask_vol))
+    }
+
+    /// Returns the volume resting on a side within `bps` basis points of mid.
+    pub fn depth(&self, side: Side, bps: f64) -> f64 {
+        let mid = match self.mid_price() {
+            Some(mid) => mid,
+            None => return 0.0,
+        };
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        levels
+            .values()
+            .filter(|l| (l.price / mid - 1.0).abs() * 10_000.0 <= bps)
+            .map(|l| l.volume)
+            .sum()
+    }
+
+    /// Estimates the order arrival density `k`, assuming resting volume decays exponentially
+    /// with the distance from mid. For that profile the volume weighted mean distance is 1/k.
+    /// Distances are in percent of mid, the unit of the configured `order_density`, so a book
+    /// whose volume sits 2 bps from mid on average gives 50.
+    pub fn order_density(&self) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let (mut weighted, mut total) = (0.0, 0.0);
+        for level in self.bids.values().chain(self.asks.values()) {
+            weighted += (level.price / mid - 1.0).abs() * 100.0 * level.volume;
+            total += level.volume;
+        }
+        if total == 0.0 || weighted == 0.0 {
+            return None;
+        }
+        Some(total / weighted)
+    }
+}
+
+fn price_key(price: &str) -> Option<u64> {
+    price.replace('.', "").parse::<u64>().ok()
+}
+
+fn strip_number(s: &str) -> String {
+    let digits = s.replace('.', "");
+    let stripped = digits.trim_start_matches('0');
+    if stripped.is_empty() {
+        "0".to_string()
+    } else {
+        stripped.to_string()
+    }
+}
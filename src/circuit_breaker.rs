@@ -0,0 +1,89 @@
+use crate::config::CircuitBreakerConfig;
+
+const JUMP_CONFIRMATIONS: usize = 3; // Consecutive jumps to the same level accepted as a real move
+
+/// Tracks the health of a pair's market data. Quoting should stop while `check` reports a
+/// problem and resume once it clears.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    last_ticker: u64,
+    last_ohlc: u64,
+    ticker_mid: f64,
+    ohlc_close: f64,
+    rejected_jumps: usize,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        CircuitBreaker {
+            config,
+            last_ticker: 0,
+            last_ohlc: 0,
+            ticker_mid: 0.0,
+            ohlc_close: 0.0,
+            rejected_jumps: 0,
+        }
+    }
+
+    pub fn on_ticker(&mut self, now: u64, mid: f64) {
+        self.last_ticker = now;
+        self.ticker_mid = mid;
+    }
+
+    pub fn on_ohlc(&mut self, now: u64, close: f64) {
+        self.last_ohlc = now;
+        self.ohlc_close = close;
+    }
+
+    /// Returns false if `price` jumped too far from `reference` to be trusted. A jump repeated
+    /// JUMP_CONFIRMATIONS times in a row is accepted as a genuine move.
+    ///
+    /// # Arguments
+    ///
+    /// * `volatility` - Standard deviation of 1 minute log returns, if known.
+    pub fn accept_price(&mut self, price: f64, reference: f64, volatility: Option<f64>) -> bool {
+        if reference == 0.0 {
+            return true;
+        }
+        let threshold = volatility
+            .map(|sigma| sigma * self.config.jump_sigmas)
+            .unwrap_or_default()
+            .max(self.config.min_jump_bps / 10_000.0);
+        if (price / reference).ln().abs() <= threshold {
+            self.rejected_jumps = 0;
+            return true;
+        }
+
+        self.rejected_jumps += 1;
+        if self.rejected_jumps >= JUMP_CONFIRMATIONS {
+            self.rejected_jumps = 0;
+            return true;
+        }
+        false
+    }
+
+    /// Returns a description of the first problem found with the market data, if any.
+    pub fn check(&self, now: u64) -> Option<String> {
+        let last_update = self.last_ticker.max(self.last_ohlc);
+        if last_update == 0 {
+            return None; // Not started yet, nothing to quote off either
+        }
+        if now.saturating_sub(last_update) > self.config.max_staleness {
+            return Some(format!("no data for {}s", now - last_update));
+        }
+        if self.rejected_jumps > 0 {
+            return Some("price jump".to_string());
+        }
+
+        let both_fresh = now.saturating_sub(self.last_ticker) <= self.config.max_staleness
+            && now.saturating_sub(self.last_ohlc) <= self.config.max_staleness;
+        if both_fresh && self.ticker_mid != 0.0 && self.ohlc_close != 0.0 {
+            let divergence = (self.ticker_mid / self.ohlc_close - 1.0).abs() * 10_000.0;
+            if divergence > self.config.max_divergence_bps {
+                return Some(format!("ticker and OHLC diverge by {:.0} bps", divergence));
+            }
+        }
+        None
+    }
+}
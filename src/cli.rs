@@ -0,0 +1,110 @@
+use crate::ledger::LotMethod;
+use crate::report::REBALANCE_PERIOD;
+use clap::{Args, Parser, Subcommand};
+
+/// Rebalances a Kraken portfolio towards equal weights by market making on each pair.
+#[derive(Parser, Debug)]
+#[command(name = "rebalancer", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+    /// Defaults to `run`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Args, Debug)]
+pub struct GlobalArgs {
+    #[arg(
+        long,
+        global = true,
+        env = "CONFIG_PATH",
+        default_value = "config.toml"
+    )]
+    pub config: String,
+    #[arg(
+        long,
+        global = true,
+        env = "JOURNAL_PATH",
+        default_value = "journal.jsonl"
+    )]
+    pub journal: String,
+    /// Log filter, e.g. "info" or "rebalancer=debug".
+    #[arg(long, global = true, env = "LOG_LEVEL")]
+    pub log_level: Option<String>,
+    /// Comma separated pairs to trade instead of the configured ones, e.g. "ETH/USD,XBT/USD".
+    /// Pairs missing from the config use the default settings.
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub pairs: Vec<String>,
+    /// Address for the API to listen on, and for `rebalance` to reach it on, instead of the
+    /// configured `api_bind`.
+    #[arg(long, global = true, env = "API_BIND")]
+    pub api_bind: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Trade the configured pairs.
+    Run,
+    /// Quote against live market data, filling orders locally when the ticker trades through
+    /// them instead of sending them.
+    Paper {
+        /// Journal for the paper session, kept apart from the live one.
+        #[arg(long, default_value = "paper.jsonl")]
+        paper_journal: String,
+    },
+    /// Replay recordings for every pair as fast as possible and summarize the orders placed.
+    Backtest {
+        #[arg(required = true)]
+        recordings: Vec<String>,
+    },
+    /// Print the account balances.
+    Balances,
+    /// Print the account's open orders.
+    Orders,
+    /// Cancel every open order on the account.
+    CancelAll,
+    /// Rebalance every pair now, through the API of the running instance.
+    Rebalance {
+        /// Print the trades needed to reach the target weights instead.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print performance against the HODL and periodic rebalance benchmarks.
+    Report {
+        #[arg(long)]
+        json: bool,
+        #[arg(long, default_value_t = REBALANCE_PERIOD)]
+        rebalance_period: u64, // seconds
+    },
+    /// Write trades.csv and gains.csv from the journal's fills.
+    ExportTrades {
+        #[arg(long, default_value = "fifo")]
+        method: LotMethod,
+        #[arg(long, default_value = ".")]
+        out: String,
+    },
+    /// Replay recordings through one pair's market and write the messages it would have sent.
+    Replay {
+        #[arg(long)]
+        pair: String,
+        /// Multiple of the recorded speed, replays without waiting if not set.
+        #[arg(long)]
+        speed: Option<f64>,
+        #[arg(long, default_value = "replay.jsonl")]
+        out: String,
+        #[arg(required = true)]
+        recordings: Vec<String>,
+    },
+    /// Encrypt an API key pair into a new keystore, see KRAKEN_KEYSTORE.
+    Keystore { path: String },
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+/// Inspect the configuration.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Check the config file for errors.
+    Validate,
+}
@@ -0,0 +1,51 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time;
+
+pub type SharedClock = Arc<dyn Clock>;
+
+/// Source of the current time, so time dependent logic can also run on recorded time.
+pub trait Clock: Send + Sync {
+    /// Returns the unix time in seconds.
+    fn now(&self) -> f64;
+
+    fn now_secs(&self) -> u64 {
+        self.now() as u64
+    }
+
+    fn now_millis(&self) -> u64 {
+        (self.now() * 1000.0) as u64
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        time::UNIX_EPOCH.elapsed().unwrap().as_secs_f64()
+    }
+}
+
+/// A clock that only moves when set, e.g. to the time of each replayed message.
+#[derive(Default)]
+pub struct SimulatedClock {
+    time: AtomicU64, // f64 bits
+}
+
+impl SimulatedClock {
+    pub fn new(time: f64) -> Self {
+        SimulatedClock {
+            time: AtomicU64::new(time.to_bits()),
+        }
+    }
+
+    pub fn set(&self, time: f64) {
+        self.time.store(time.to_bits(), Ordering::Relaxed);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> f64 {
+        f64::from_bits(self.time.load(Ordering::Relaxed))
+    }
+}
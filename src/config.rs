@@ -0,0 +1,507 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tracing::info;
+
+const DEFAULT_PAIRS: [&str; 4] = ["ETH/USD", "XBT/USD", "SOL/USD", "ARB/USD"];
+const ORDER_CREATION_COOLDOWN: u64 = 300; // seconds
+const API_PORT: u16 = 8080;
+const API_BIND: &str = "127.0.0.1";
+const CACHE_DIR: &str = "cache";
+
+/// Top level configuration, loaded from a TOML file.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Config {
+    pub pairs: Vec<PairConfig>,
+    /// Port for the status and control API.
+    #[serde(default = "default_api_port")]
+    pub api_port: u16,
+    /// Address the API listens on. Only local by default since the control routes move money.
+    #[serde(default = "default_api_bind")]
+    pub api_bind: String,
+    #[serde(default)]
+    pub kill_switch: KillSwitchConfig,
+    /// Records raw WebSocket traffic when set.
+    pub recorder: Option<RecorderConfig>,
+    #[serde(default)]
+    pub endpoints: Endpoints,
+    /// Directory market history is cached in, used when the REST API is unavailable.
+    #[serde(default = "default_cache_dir")]
+    pub cache_dir: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairConfig {
+    pub pair: String,
+    #[serde(default)]
+    pub strategy: StrategyConfig,
+    #[serde(default)]
+    pub ladder: LadderConfig,
+    #[serde(default)]
+    pub volatility: VolatilityConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub orders: OrderConfig,
+    /// Minimum time between placing new orders.
+    #[serde(default = "default_order_cooldown")]
+    pub order_cooldown: u64, // seconds
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StrategyConfig {
+    Threshold(ThresholdConfig),
+    AvellanedaStoikov(AvellanedaStoikovConfig),
+}
+
+impl Default for StrategyConfig {
+    fn default() -> Self {
+        StrategyConfig::Threshold(ThresholdConfig::default())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThresholdConfig {
+    pub order_size_usd: f64,
+    pub delta_threshold: f64, // In percentage
+}
+
+impl Default for ThresholdConfig {
+    fn default() -> Self {
+        ThresholdConfig {
+            order_size_usd: 30.0,
+            delta_threshold: 1.5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AvellanedaStoikovConfig {
+    pub order_size_usd: f64,
+    pub risk_aversion: f64,
+    /// Order arrival density `k`, per percent of distance from mid. 50 means resting volume sits
+    /// 2 bps from mid on average.
+    pub order_density: f64,
+    /// Estimate the order density from the local order book instead of `order_density`.
+    pub book_density: bool,
+    pub base_volatility: f64, // Added to the 1 minute volatility estimate
+}
+
+impl Default for AvellanedaStoikovConfig {
+    fn default() -> Self {
+        AvellanedaStoikovConfig {
+            order_size_usd: 30.0,
+            risk_aversion: 15.0,
+            order_density: 50.0,
+            book_density: false,
+            base_volatility: 0.0005,
+        }
+    }
+}
+
+/// Expands each quote into `levels` orders, each further from mid than the last.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LadderConfig {
+    pub levels: usize,
+    pub spacing: Spacing,
+    /// Each level's size is the previous level's size times this.
+    pub size_scale: f64,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        LadderConfig {
+            levels: 1,
+            spacing: Spacing::Bps(10.0),
+            size_scale: 1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum Spacing {
+    /// Fixed distance between levels, in basis points.
+    Bps(f64),
+    /// Distance between levels as a multiple of the current volatility.
+    Volatility(f64),
+}
+
+/// Volatility estimator used for a pair. Price based estimators use the prices sampled every
+/// few seconds, bar based ones use completed 1 minute OHLC bars.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VolatilityConfig {
+    Stdev {
+        window: usize,
+    },
+    Ewma {
+        halflife: f64, // In samples
+        #[serde(default = "default_min_samples")]
+        min_samples: usize,
+    },
+    Parkinson {
+        window: usize,
+    },
+    GarmanKlass {
+        window: usize,
+    },
+    Blend {
+        short: Box<VolatilityConfig>,
+        long: Box<VolatilityConfig>,
+        weight: f64, // Weight of the short estimate
+    },
+}
+
+impl Default for VolatilityConfig {
+    fn default() -> Self {
+        VolatilityConfig::Stdev { window: 100 }
+    }
+}
+
+/// Portfolio wide limits that halt trading until manually re-enabled.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KillSwitchConfig {
+    pub max_daily_loss_pct: f64,
+    pub max_drawdown_pct: f64,
+    pub max_fills_per_hour: usize,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        KillSwitchConfig {
+            max_daily_loss_pct: 5.0,
+            max_drawdown_pct: 10.0,
+            max_fills_per_hour: 60,
+        }
+    }
+}
+
+/// Pre-trade limits. Orders are clipped to the size limits and rejected outright if they
+/// breach the others.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RiskConfig {
+    pub max_order_notional: f64, // USD
+    /// Maximum share of the portfolio held in the pair's asset, e.g. 0.5.
+    pub max_position_weight: Option<f64>,
+    pub min_cash_reserve: f64, // USD
+    pub price_collar_bps: f64, // Maximum distance from mid
+    pub max_open_orders: usize,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        RiskConfig {
+            max_order_notional: 100.0,
+            max_position_weight: None,
+            min_cash_reserve: 0.0,
+            price_collar_bps: 200.0,
+            max_open_orders: 10,
+        }
+    }
+}
+
+/// Base URLs of the Kraken APIs, overridable to run against a mock exchange.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Endpoints {
+    pub rest: String,
+    pub public_ws: String,
+    pub private_ws: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Endpoints {
+            rest: "https://api.kraken.com".to_string(),
+            public_ws: "wss://ws.kraken.com".to_string(),
+            private_ws: "wss://ws-auth.kraken.com".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RecorderConfig {
+    pub dir: String,
+    pub max_file_size_mb: u64, // Uncompressed
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        RecorderConfig {
+            dir: "recordings".to_string(),
+            max_file_size_mb: 100,
+        }
+    }
+}
+
+/// Flags sent with every order for the pair.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct OrderConfig {
+    /// Rejects quotes that would cross the spread instead of paying taker fees.
+    pub post_only: bool,
+    pub time_in_force: Option<TimeInForce>,
+    /// Lifetime of each order, sent as `expiretm`.
+    pub expire_after: Option<u64>, // seconds
+}
+
+impl Default for OrderConfig {
+    fn default() -> Self {
+        OrderConfig {
+            post_only: true,
+            time_in_force: None,
+            expire_after: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TimeInForce {
+    Gtc,
+    Ioc,
+    Gtd,
+}
+
+impl TimeInForce {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimeInForce::Gtc => "GTC",
+            TimeInForce::Ioc => "IOC",
+            TimeInForce::Gtd => "GTD",
+        }
+    }
+}
+
+/// Market data health checks that pause quoting for the pair while breached.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub max_staleness: u64, // seconds without ticker or OHLC data
+    /// Price moves beyond this many standard deviations are treated as bad ticks.
+    pub jump_sigmas: f64,
+    pub min_jump_bps: f64,       // Moves below this are never treated as jumps
+    pub max_divergence_bps: f64, // Between ticker mid and OHLC close
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            max_staleness: 300,
+            jump_sigmas: 10.0,
+            min_jump_bps: 50.0,
+            max_divergence_bps: 100.0,
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from `path`, falling back to the defaults if the file doesn't exist.
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(toml::from_str(&contents)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(path, "Config not found, using defaults");
+                Ok(Config::default())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            pairs: DEFAULT_PAIRS
+                .iter()
+                .map(|pair| PairConfig::new(pair))
+                .collect(),
+            api_port: API_PORT,
+            api_bind: API_BIND.to_string(),
+            kill_switch: KillSwitchConfig::default(),
+            recorder: None,
+            endpoints: Endpoints::default(),
+            cache_dir: CACHE_DIR.to_string(),
+        }
+    }
+}
+
+impl PairConfig {
+    /// Returns the default settings for `pair`.
+    pub fn new(pair: &str) -> Self {
+        PairConfig {
+            pair: pair.to_string(),
+            strategy: StrategyConfig::default(),
+            ladder: LadderConfig::default(),
+            volatility: VolatilityConfig::default(),
+            risk: RiskConfig::default(),
+            circuit_breaker: CircuitBreakerConfig::default(),
+            orders: OrderConfig::default(),
+            order_cooldown: ORDER_CREATION_COOLDOWN,
+        }
+    }
+
+    /// Returns every problem with the pair's settings.
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let mut problem = |message: &str| problems.push(format!("{}: {}", self.pair, message));
+
+        if !self.pair.ends_with("/USD") {
+            problem("only /USD pairs are supported");
+        }
+        let order_size_usd = match &self.strategy {
+            StrategyConfig::Threshold(config) => config.order_size_usd,
+            StrategyConfig::AvellanedaStoikov(config) => config.order_size_usd,
+        };
+        if order_size_usd <= 0.0 {
+            problem("order_size_usd must be positive");
+        }
+        if self.ladder.levels == 0 {
+            problem("ladder levels must be at least 1");
+        }
+        if self.ladder.size_scale <= 0.0 {
+            problem("ladder size_scale must be positive");
+        }
+        let (Spacing::Bps(spacing) | Spacing::Volatility(spacing)) = self.ladder.spacing;
+        if spacing <= 0.0 {
+            problem("ladder spacing must be positive");
+        }
+        if let Some(message) = validate_volatility(&self.volatility) {
+            problem(&message);
+        }
+        if self.risk.price_collar_bps <= 0.0 {
+            problem("risk price_collar_bps must be positive");
+        }
+        if let Some(weight) = self.risk.max_position_weight {
+            if weight <= 0.0 || weight > 1.0 {
+                problem("risk max_position_weight must be in (0, 1]");
+            }
+        }
+        if self.risk.max_open_orders < 2 * self.ladder.levels {
+            problem("risk max_open_orders is below two orders per ladder level");
+        }
+        if matches!(self.orders.time_in_force, Some(TimeInForce::Gtd))
+            && self.orders.expire_after.is_none()
+        {
+            problem("orders with time_in_force GTD need expire_after");
+        }
+        problems
+    }
+}
+
+impl Config {
+    /// Restricts the config to `pairs`, using the defaults for any that aren't configured.
+    pub fn select_pairs(&mut self, pairs: &[String]) {
+        self.pairs = pairs
+            .iter()
+            .map(|pair| {
+                self.pairs
+                    .iter()
+                    .find(|p| p.pair == *pair)
+                    .cloned()
+                    .unwrap_or_else(|| PairConfig::new(pair))
+            })
+            .collect();
+    }
+
+    /// Returns the base URL of the running instance's API. An API listening on every interface
+    /// is reached over loopback.
+    pub fn api_url(&self) -> String {
+        match self.api_bind.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) if ip.is_unspecified() => {
+                format!(
+                    "http://{}",
+                    SocketAddr::from((Ipv4Addr::LOCALHOST, self.api_port))
+                )
+            }
+            Ok(IpAddr::V6(ip)) if ip.is_unspecified() => {
+                format!(
+                    "http://{}",
+                    SocketAddr::from((Ipv6Addr::LOCALHOST, self.api_port))
+                )
+            }
+            Ok(ip) => format!("http://{}", SocketAddr::new(ip, self.api_port)),
+            Err(_) => format!("http://{}:{}", self.api_bind, self.api_port), // A hostname
+        }
+    }
+
+    /// Returns every problem found in the config, empty if it's valid.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.pairs.is_empty() {
+            problems.push("no pairs configured".to_string());
+        }
+        for (i, pair) in self.pairs.iter().enumerate() {
+            if self.pairs[..i].iter().any(|p| p.pair == pair.pair) {
+                problems.push(format!("{}: configured more than once", pair.pair));
+            }
+            problems.extend(pair.validate());
+        }
+        if self.kill_switch.max_daily_loss_pct <= 0.0 || self.kill_switch.max_drawdown_pct <= 0.0 {
+            problems.push("kill_switch loss limits must be positive".to_string());
+        }
+        if let Some(recorder) = &self.recorder {
+            if recorder.max_file_size_mb == 0 {
+                problems.push("recorder max_file_size_mb must be positive".to_string());
+            }
+        }
+        problems
+    }
+}
+
+fn validate_volatility(config: &VolatilityConfig) -> Option<String> {
+    match config {
+        VolatilityConfig::Stdev { window }
+        | VolatilityConfig::Parkinson { window }
+        | VolatilityConfig::GarmanKlass { window }
+            if *window < 2 =>
+        {
+            Some("volatility window must be at least 2".to_string())
+        }
+        VolatilityConfig::Ewma { halflife, .. } if *halflife <= 0.0 => {
+            Some("volatility halflife must be positive".to_string())
+        }
+        VolatilityConfig::Blend {
+            short,
+            long,
+            weight,
+        } => {
+            if !(0.0..=1.0).contains(weight) {
+                return Some("volatility blend weight must be in [0, 1]".to_string());
+            }
+            validate_volatility(short).or_else(|| validate_volatility(long))
+        }
+        _ => None,
+    }
+}
+
+fn default_api_port() -> u16 {
+    API_PORT
+}
+
+fn default_api_bind() -> String {
+    API_BIND.to_string()
+}
+
+fn default_cache_dir() -> String {
+    CACHE_DIR.to_string()
+}
+
+fn default_order_cooldown() -> u64 {
+    ORDER_CREATION_COOLDOWN
+}
+
+fn default_min_samples() -> usize {
+    10
+}
@@ -0,0 +1,110 @@
+use crate::account::Portfolio;
+use crate::clock::SharedClock;
+use crate::config::KillSwitchConfig;
+use crate::journal::{Event, Journal};
+use crate::kill_switch::KillSwitch;
+use crate::product::Market;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{error, Instrument};
+
+const KILL_SWITCH_INTERVAL: u64 = 10; // seconds
+
+/// Live markets keyed by pair, registered by each task once it is connected.
+pub type Markets = Arc<Mutex<HashMap<String, Arc<Mutex<Market>>>>>;
+
+/// Operator controls shared by all markets. Lives outside `Market` so it survives restarts.
+#[derive(Debug)]
+pub struct Controls {
+    paused: bool,
+    paused_pairs: HashSet<String>,
+    pub kill_switch: KillSwitch,
+}
+
+impl Controls {
+    pub fn new(kill_switch: KillSwitchConfig) -> Self {
+        Controls {
+            paused: false,
+            paused_pairs: HashSet::new(),
+            kill_switch: KillSwitch::new(kill_switch),
+        }
+    }
+
+    /// Returns true if quoting is paused for the pair, either globally or individually, or
+    /// the kill switch has tripped.
+    pub fn is_paused(&self, pair: &str) -> bool {
+        self.paused || self.paused_pairs.contains(pair) || self.kill_switch.is_tripped()
+    }
+
+    pub fn is_paused_globally(&self) -> bool {
+        self.paused
+    }
+
+    pub fn get_paused_pairs(&self) -> Vec<String> {
+        self.paused_pairs.iter().cloned().collect()
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn pause_pair(&mut self, pair: &str) {
+        self.paused_pairs.insert(pair.to_string());
+    }
+
+    pub fn resume_pair(&mut self, pair: &str) {
+        self.paused_pairs.remove(pair);
+    }
+}
+
+/// Feeds the portfolio value to the kill switch. When it trips, journals the reason and
+/// cancels every order; quoting stays halted until the switch is reset.
+pub async fn monitor_kill_switch(
+    portfolio: Arc<Mutex<Portfolio>>,
+    controls: Arc<Mutex<Controls>>,
+    markets: Markets,
+    journal: Arc<Mutex<Journal>>,
+    clock: SharedClock,
+) {
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(KILL_SWITCH_INTERVAL));
+    loop {
+        interval.tick().await;
+        let value = {
+            let portfolio = portfolio.lock().await;
+            if !portfolio.has_prices() {
+                continue;
+            }
+            portfolio.get_total_value()
+        };
+
+        let now = clock.now_secs();
+        let reason = {
+            let mut controls = controls.lock().await;
+            match controls.kill_switch.check(now, value) {
+                Some(reason) => {
+                    controls.kill_switch.trip(reason.clone());
+                    reason
+                }
+                None => continue,
+            }
+        };
+
+        error!(%reason, value, "Kill switch tripped, cancelling all orders");
+        journal
+            .lock()
+            .await
+            .append(Event::KillSwitchTripped { reason });
+        let markets: Vec<Arc<Mutex<Market>>> = markets.lock().await.values().cloned().collect();
+        for market in markets {
+            let mut market = market.lock().await;
+            let span = market.span();
+            market.cancel_all_orders().instrument(span).await;
+        }
+    }
+}
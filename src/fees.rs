@@ -0,0 +1,75 @@
+use crate::account::{Portfolio, Signer};
+use crate::metrics::METRICS;
+use crate::product::{PRICE_UPDATE_DENOMINATOR, PRICE_UPDATE_NUMERATOR};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::info;
+
+pub const FEE_REFRESH_INTERVAL: u64 = 3600; // seconds
+
+// Used until the schedule is fetched from TradeVolume
+const DEFAULT_MAKER_FEE: f64 = 0.0005;
+const DEFAULT_TAKER_FEE: f64 = 0.004;
+
+/// Fee rates for a pair as fractions of the notional, e.g. 0.0016 for 0.16%.
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct Fees {
+    pub maker: f64,
+    pub taker: f64,
+}
+
+impl Default for Fees {
+    fn default() -> Self {
+        Fees {
+            maker: DEFAULT_MAKER_FEE,
+            taker: DEFAULT_TAKER_FEE,
+        }
+    }
+}
+
+impl Fees {
+    /// Returns the narrowest spread worth quoting, covering the maker fee on both legs.
+    pub fn min_spread(&self) -> f64 {
+        4.0 * self.maker * PRICE_UPDATE_NUMERATOR / PRICE_UPDATE_DENOMINATOR
+    }
+
+    /// Returns the fees for buying and selling back, entering as a taker if `taker` is set.
+    pub fn round_trip(&self, taker: bool) -> f64 {
+        let entry = if taker { self.taker } else { self.maker };
+        entry + self.maker
+    }
+
+    /// Returns true if capturing `spread`, as a fraction of the price, pays for a round trip.
+    pub fn is_profitable(&self, spread: f64, taker: bool) -> bool {
+        spread > self.round_trip(taker)
+    }
+}
+
+/// Periodically fetches each pair's fee rates, which change with the 30 day volume tier.
+pub async fn refresh(
+    portfolio: Arc<Mutex<Portfolio>>,
+    signer: Arc<Mutex<Signer>>,
+    pairs: Vec<String>,
+) {
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(FEE_REFRESH_INTERVAL));
+    loop {
+        interval.tick().await;
+        for pair in pairs.iter() {
+            let fees = { signer.lock().await.get_trade_fees(pair).await };
+            if let Some(fees) = fees {
+                info!(pair, maker = fees.maker, taker = fees.taker, "Updated fees");
+                METRICS
+                    .fee_rate
+                    .with_label_values(&[pair, "maker"])
+                    .set(fees.maker);
+                METRICS
+                    .fee_rate
+                    .with_label_values(&[pair, "taker"])
+                    .set(fees.taker);
+                portfolio.lock().await.set_fees(pair.clone(), fees);
+            }
+        }
+    }
+}
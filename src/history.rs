@@ -0,0 +1,133 @@
+use crate::clock::Clock;
+use crate::metrics::METRICS;
+use crate::volatility::Bar;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::time::Instant;
+use tracing::{info, warn};
+
+const CACHE_MAX_AGE: u64 = 3600; // seconds
+const OHLC_INTERVAL: u64 = 1; // minutes
+
+/// Recent market history used to warm start a market.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct History {
+    pub bars: Vec<Bar>,
+    pub spreads: Vec<(f64, f64, f64)>, // (time, bid, ask)
+    #[serde(default)]
+    pub order_min: f64, // Smallest order volume the exchange accepts
+}
+
+impl History {
+    pub fn last_updated(&self) -> f64 {
+        self.bars.last().map(|bar| bar.time).unwrap_or_default()
+    }
+}
+
+/// Fetches recent OHLC bars and spreads from the REST API, falling back to the cache in
+/// `cache_dir` if the request fails. Returns an empty history if neither is available.
+pub async fn load(base_url: &str, cache_dir: &str, pair: &str, clock: &dyn Clock) -> History {
+    match fetch(base_url, pair).await {
+        Ok(history) => {
+            if let Err(e) = save_cache(cache_dir, pair, &history) {
+                warn!(pair, error = %e, "Failed to cache history");
+            }
+            history
+        }
+        Err(e) => {
+            warn!(pair, error = %e, "Failed to fetch history");
+            load_cache(cache_dir, pair, clock).unwrap_or_default()
+        }
+    }
+}
+
+async fn fetch(base_url: &str, pair: &str) -> Result<History, Box<dyn Error>> {
+    let client = reqwest::Client::new();
+    let rest_pair = pair.replace('/', "");
+
+    let url = format!(
+        "{}/0/public/OHLC?pair={}&interval={}",
+        base_url, rest_pair, OHLC_INTERVAL
+    );
+    let start = Instant::now();
+    let json: serde_json::Value = client.get(url).send().await?.json().await?;
+    METRICS.observe_rest("/0/public/OHLC", start);
+    let mut bars = Vec::new();
+    for row in get_rows(&json)? {
+        // [time, open, high, low, close, vwap, volume, count], time is the start of the bar
+        let field = |i: usize| row[i].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+        bars.push(Bar {
+            time: row[0].as_f64().unwrap_or(0.0) + (OHLC_INTERVAL * 60) as f64,
+            open: field(1),
+            high: field(2),
+            low: field(3),
+            close: field(4),
+        });
+    }
+    bars.pop(); // The last bar is still in progress
+
+    let url = format!("{}/0/public/Spread?pair={}", base_url, rest_pair);
+    let start = Instant::now();
+    let json: serde_json::Value = client.get(url).send().await?.json().await?;
+    METRICS.observe_rest("/0/public/Spread", start);
+    let mut spreads = Vec::new();
+    for row in get_rows(&json)? {
+        // [time, bid, ask]
+        let field = |i: usize| row[i].as_str().unwrap_or("0").parse::<f64>().unwrap_or(0.0);
+        spreads.push((row[0].as_f64().unwrap_or(0.0), field(1), field(2)));
+    }
+
+    let url = format!("{}/0/public/AssetPairs?pair={}", base_url, rest_pair);
+    let start = Instant::now();
+    let json: serde_json::Value = client.get(url).send().await?.json().await?;
+    METRICS.observe_rest("/0/public/AssetPairs", start);
+    let order_min = json["result"]
+        .as_object()
+        .and_then(|result| result.values().next())
+        .and_then(|info| info["ordermin"].as_str())
+        .and_then(|ordermin| ordermin.parse::<f64>().ok())
+        .ok_or("Missing ordermin")?;
+
+    Ok(History {
+        bars,
+        spreads,
+        order_min,
+    })
+}
+
+/// Returns the rows of a public endpoint's result, which is keyed by Kraken's pair name.
+fn get_rows(json: &serde_json::Value) -> Result<&Vec<serde_json::Value>, Box<dyn Error>> {
+    if let Some(errors) = json["error"].as_array() {
+        if !errors.is_empty() {
+            return Err(format!("{:?}", errors).into());
+        }
+    }
+    json["result"]
+        .as_object()
+        .and_then(|result| result.iter().find(|(key, _)| *key != "last"))
+        .and_then(|(_, rows)| rows.as_array())
+        .ok_or_else(|| "Unexpected response".into())
+}
+
+fn cache_path(cache_dir: &str, pair: &str) -> String {
+    format!("{}/{}.json", cache_dir, pair.replace('/', ""))
+}
+
+fn save_cache(cache_dir: &str, pair: &str, history: &History) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(cache_dir)?;
+    fs::write(cache_path(cache_dir, pair), serde_json::to_string(history)?)?;
+    Ok(())
+}
+
+/// Returns the cached history if it is recent enough to be useful.
+fn load_cache(cache_dir: &str, pair: &str, clock: &dyn Clock) -> Option<History> {
+    let contents = fs::read_to_string(cache_path(cache_dir, pair)).ok()?;
+    let history: History = serde_json::from_str(&contents).ok()?;
+    if clock.now() - history.last_updated() > CACHE_MAX_AGE as f64 {
+        info!(pair, "Cached history is too old, ignoring");
+        return None;
+    }
+    info!(pair, "Using cached history");
+    Some(history)
+}
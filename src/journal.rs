@@ -0,0 +1,201 @@
+use crate::clock::SharedClock;
+use crate::pnl::Pnl;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use tracing::{info, warn};
+
+/// A single journal line.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub time: u64, // unix seconds
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// An addOrder request was sent.
+    OrderPlaced {
+        pair: String,
+        side: String,
+        price: f64,
+        volume: f64,
+    },
+    /// The exchange reported the order as open.
+    OrderOpened {
+        pair: String,
+        order_id: String,
+        side: String,
+        price: f64,
+        volume: f64,
+    },
+    OrderCanceled {
+        pair: String,
+        order_id: String,
+    },
+    Fill {
+        pair: String,
+        order_id: String,
+        side: String,
+        price: f64,
+        volume: f64,
+        fee: f64, // In USD
+    },
+    /// The portfolio's (amount, price) per asset.
+    Balances {
+        assets: HashMap<String, (f64, f64)>,
+    },
+    MarketState(MarketState),
+    KillSwitchTripped {
+        reason: String,
+    },
+    KillSwitchReset {
+        #[serde(default)]
+        by: String, // Operator and address that reset it
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MarketState {
+    pub pair: String,
+    pub last_price: f64,
+    pub last_order_time: u64,
+    pub strategy: Option<serde_json::Value>,
+}
+
+/// Append-only JSONL journal of orders, fills, balances and market state. Replayed on open so
+/// the latest state can be restored after a restart.
+pub struct Journal {
+    file: Option<File>,
+    balances: Option<HashMap<String, (f64, f64)>>,
+    markets: HashMap<String, MarketState>,
+    kill_switch: Option<String>,
+    pnl: Pnl,
+    clock: SharedClock,
+}
+
+impl Journal {
+    pub fn open(path: &str, clock: SharedClock) -> std::io::Result<Self> {
+        let mut journal = Journal {
+            file: Some(OpenOptions::new().create(true).append(true).open(path)?),
+            ..Journal::memory(clock)
+        };
+
+        let entries = read_entries(path)?;
+        info!(entries = entries.len(), path, "Replaying journal");
+        for entry in entries.iter() {
+            journal.apply(entry);
+        }
+        Ok(journal)
+    }
+
+    /// Returns a journal that isn't written to disk, e.g. for replays.
+    pub fn memory(clock: SharedClock) -> Self {
+        Journal {
+            file: None,
+            balances: None,
+            markets: HashMap::new(),
+            kill_switch: None,
+            pnl: Pnl::default(),
+            clock,
+        }
+    }
+
+    pub fn append(&mut self, event: Event) {
+        let entry = Entry {
+            time: self.clock.now_secs(),
+            event,
+        };
+        self.apply(&entry);
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => return,
+        };
+        let line = serde_json::to_string(&entry).unwrap();
+        if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+            warn!(error = %e, "Failed to write journal entry");
+        }
+    }
+
+    /// Tracks the latest state so it can be restored without re-reading the file.
+    fn apply(&mut self, entry: &Entry) {
+        match &entry.event {
+            Event::Balances { assets } => {
+                // Start tracking profit once every asset has a price to take its cost at
+                if !self.pnl.is_started() && assets.values().all(|(_, price)| *price != 0.0) {
+                    self.pnl.start(entry.time, assets);
+                }
+                self.balances = Some(assets.clone());
+            }
+            Event::Fill {
+                pair,
+                side,
+                price,
+                volume,
+                fee,
+                ..
+            } => {
+                let asset = pair.strip_suffix("/USD").unwrap_or(pair);
+                self.pnl
+                    .record_fill(entry.time, asset, side, *volume, *price, *fee);
+                // Keep the balances current in case no snapshot follows the fill
+                if let Some(balances) = self.balances.as_mut() {
+                    let signed = if side == "buy" { *volume } else { -volume };
+                    if let Some((amount, last)) = balances.get_mut(asset) {
+                        *amount += signed;
+                        *last = *price;
+                    }
+                    if let Some((amount, _)) = balances.get_mut("ZUSD") {
+                        *amount -= signed * price + fee;
+                    }
+                }
+            }
+            Event::MarketState(state) => {
+                self.markets.insert(state.pair.clone(), state.clone());
+            }
+            Event::KillSwitchTripped { reason } => self.kill_switch = Some(reason.clone()),
+            Event::KillSwitchReset { .. } => self.kill_switch = None,
+            _ => {}
+        }
+    }
+
+    /// Returns the latest journaled (amount, price) per asset.
+    pub fn get_balances(&self) -> Option<&HashMap<String, (f64, f64)>> {
+        self.balances.as_ref()
+    }
+
+    /// Returns the reason the kill switch tripped if it hasn't been reset since.
+    pub fn get_kill_switch(&self) -> Option<&str> {
+        self.kill_switch.as_deref()
+    }
+
+    pub fn get_pnl(&self) -> &Pnl {
+        &self.pnl
+    }
+
+    pub fn get_market_state(&self, pair: &str) -> Option<&MarketState> {
+        self.markets.get(pair)
+    }
+}
+
+/// Reads all entries from a journal file, skipping lines that fail to parse (e.g. a partial
+/// write before a crash).
+pub fn read_entries(path: &str) -> std::io::Result<Vec<Entry>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => warn!(error = %e, line, "Skipping journal line"),
+        }
+    }
+    Ok(entries)
+}
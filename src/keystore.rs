@@ -0,0 +1,140 @@
+use crate::auth::{Credentials, CredentialsError};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use zeroize::{Zeroize, Zeroizing};
+
+const VERSION: u32 = 1;
+const SALT_LENGTH: usize = 16; // bytes
+
+/// An API key pair encrypted with AES-256-GCM under a key derived from a passphrase with
+/// Argon2id. Stored as JSON.
+#[derive(Serialize, Deserialize)]
+struct Keystore {
+    version: u32,
+    m_cost: u32, // KiB
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Plaintext {
+    key: String,
+    secret: String,
+}
+
+impl Drop for Plaintext {
+    fn drop(&mut self) {
+        self.key.zeroize();
+        self.secret.zeroize();
+    }
+}
+
+/// Encrypts the key pair to `path`, readable only by the owner.
+pub fn create(
+    path: &str,
+    key: &str,
+    secret: &str,
+    passphrase: &str,
+) -> Result<(), CredentialsError> {
+    Credentials::new(key, secret, None)?; // Catch typos before encrypting them
+    let params = Params::default();
+    let mut salt = [0u8; SALT_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&Plaintext {
+            key: key.trim().to_string(),
+            secret: secret.trim().to_string(),
+        })
+        .unwrap(),
+    );
+    let cipher = derive_cipher(passphrase, &salt, &params)?;
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| CredentialsError::Keystore("encryption failed".to_string()))?;
+
+    let keystore = Keystore {
+        version: VERSION,
+        m_cost: params.m_cost(),
+        t_cost: params.t_cost(),
+        p_cost: params.p_cost(),
+        salt: general_purpose::STANDARD.encode(salt),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    };
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .map_err(|e| CredentialsError::Unreadable(path.to_string(), e))?;
+    file.write_all(serde_json::to_string_pretty(&keystore).unwrap().as_bytes())
+        .map_err(|e| CredentialsError::Unreadable(path.to_string(), e))
+}
+
+/// Decrypts the key pair at `path`. A wrong passphrase and a tampered file are
+/// indistinguishable.
+pub fn open(
+    path: &str,
+    passphrase: &str,
+    otp: Option<String>,
+) -> Result<Credentials, CredentialsError> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| CredentialsError::Unreadable(path.to_string(), e))?;
+    let keystore: Keystore =
+        serde_json::from_str(&contents).map_err(|e| CredentialsError::Keystore(e.to_string()))?;
+    if keystore.version != VERSION {
+        return Err(CredentialsError::Keystore(format!(
+            "unsupported version {}",
+            keystore.version
+        )));
+    }
+    let decode = |field: &str| {
+        general_purpose::STANDARD
+            .decode(field)
+            .map_err(|e| CredentialsError::Keystore(e.to_string()))
+    };
+    let salt = decode(&keystore.salt)?;
+    let nonce = decode(&keystore.nonce)?;
+    if nonce.len() != 12 {
+        return Err(CredentialsError::Keystore("invalid nonce".to_string()));
+    }
+    let params = Params::new(keystore.m_cost, keystore.t_cost, keystore.p_cost, Some(32))
+        .map_err(|e| CredentialsError::Keystore(e.to_string()))?;
+
+    let cipher = derive_cipher(passphrase, &salt, &params)?;
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                decode(&keystore.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| CredentialsError::Keystore("wrong passphrase".to_string()))?,
+    );
+    let plaintext: Plaintext = serde_json::from_slice(&plaintext)
+        .map_err(|e| CredentialsError::Keystore(e.to_string()))?;
+    Credentials::new(&plaintext.key, &plaintext.secret, otp)
+}
+
+fn derive_cipher(
+    passphrase: &str,
+    salt: &[u8],
+    params: &Params,
+) -> Result<Aes256Gcm, CredentialsError> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut_slice())
+        .map_err(|e| CredentialsError::Keystore(e.to_string()))?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_slice())))
+}
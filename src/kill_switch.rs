@@ -0,0 +1,89 @@
+use crate::config::KillSwitchConfig;
+use std::collections::VecDeque;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+const FILL_RATE_WINDOW: u64 = 3600; // seconds
+
+/// Halts trading when the portfolio loses too much or fills arrive suspiciously fast. Once
+/// tripped it stays tripped until reset by an operator.
+#[derive(Debug)]
+pub struct KillSwitch {
+    config: KillSwitchConfig,
+    peak_value: f64,
+    day: u64,
+    day_start_value: f64,
+    fills: VecDeque<u64>, // Fill times within the window
+    tripped: Option<String>,
+}
+
+impl KillSwitch {
+    pub fn new(config: KillSwitchConfig) -> Self {
+        KillSwitch {
+            config,
+            peak_value: 0.0,
+            day: 0,
+            day_start_value: 0.0,
+            fills: VecDeque::new(),
+            tripped: None,
+        }
+    }
+
+    /// Returns the reason the switch tripped, if it has.
+    pub fn get_tripped(&self) -> Option<&str> {
+        self.tripped.as_deref()
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped.is_some()
+    }
+
+    pub fn trip(&mut self, reason: String) {
+        self.tripped = Some(reason);
+    }
+
+    /// Re-arms the switch, measuring drawdown and daily loss from the next value recorded.
+    pub fn reset(&mut self) {
+        self.tripped = None;
+        self.peak_value = 0.0;
+        self.day = 0;
+        self.fills.clear();
+    }
+
+    pub fn record_fill(&mut self, now: u64) {
+        self.fills.push_back(now);
+    }
+
+    /// Updates the tracked values and returns the reason if a limit was breached. Does not
+    /// trip the switch itself so the caller can act on it first.
+    pub fn check(&mut self, now: u64, value: f64) -> Option<String> {
+        if self.tripped.is_some() || value <= 0.0 {
+            return None;
+        }
+
+        let day = now / SECONDS_PER_DAY;
+        if day != self.day {
+            self.day = day;
+            self.day_start_value = value;
+        }
+        self.peak_value = self.peak_value.max(value);
+        while self
+            .fills
+            .front()
+            .is_some_and(|time| *time + FILL_RATE_WINDOW < now)
+        {
+            self.fills.pop_front();
+        }
+
+        let daily_loss = (1.0 - value / self.day_start_value) * 100.0;
+        let drawdown = (1.0 - value / self.peak_value) * 100.0;
+        if daily_loss > self.config.max_daily_loss_pct {
+            Some(format!("daily loss of {:.2}%", daily_loss))
+        } else if drawdown > self.config.max_drawdown_pct {
+            Some(format!("drawdown of {:.2}%", drawdown))
+        } else if self.fills.len() > self.config.max_fills_per_hour {
+            Some(format!("{} fills in the last hour", self.fills.len()))
+        } else {
+            None
+        }
+    }
+}
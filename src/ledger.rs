@@ -0,0 +1,318 @@
+use crate::journal::{Entry, Event};
+use chrono::{DateTime, Datelike, SecondsFormat};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::Write;
+use std::str::FromStr;
+
+// Volume left over from float rounding, well below Kraken's 8 decimals
+const VOLUME_EPSILON: f64 = 1e-10;
+
+/// Which lots a sale is matched against.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LotMethod {
+    Fifo, // Oldest first
+    Lifo, // Newest first
+    Hifo, // Highest cost first
+}
+
+impl FromStr for LotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(LotMethod::Fifo),
+            "lifo" => Ok(LotMethod::Lifo),
+            "hifo" => Ok(LotMethod::Hifo),
+            _ => Err(format!(
+                "Unknown lot method {}, expected fifo, lifo or hifo",
+                s
+            )),
+        }
+    }
+}
+
+/// Volume of an asset still held from a single acquisition.
+#[derive(Serialize, Debug, Clone)]
+pub struct Lot {
+    pub time: u64,
+    pub volume: f64,
+    pub price: f64, // Cost per unit, including the buy fee
+}
+
+/// An asset's open lots in acquisition order. Buy fees are part of each lot's cost and sell
+/// fees reduce the proceeds, so profit from both the ledger and `Pnl` is net of fees.
+#[derive(Debug, Default, Clone)]
+pub struct Lots {
+    lots: Vec<Lot>,
+}
+
+impl Lots {
+    /// Opens a lot for a buy. Zero volume fills are ignored.
+    pub fn buy(&mut self, time: u64, volume: f64, price: f64, fee: f64) {
+        if volume <= 0.0 {
+            return;
+        }
+        self.lots.push(Lot {
+            time,
+            volume,
+            price: (price * volume + fee) / volume,
+        });
+    }
+
+    /// Removes `volume` from the lots according to the method and returns the matches. Volume
+    /// beyond the open lots is matched with no lot and no cost.
+    pub fn sell(&mut self, method: LotMethod, volume: f64, price: f64, fee: f64) -> Vec<LotMatch> {
+        let mut matches = Vec::new();
+        if volume <= 0.0 {
+            return matches;
+        }
+        let net_price = (price * volume - fee) / volume;
+        let lots = &mut self.lots;
+        let mut remaining = volume;
+        while remaining > VOLUME_EPSILON && !lots.is_empty() {
+            let index = match method {
+                LotMethod::Fifo => 0,
+                LotMethod::Lifo => lots.len() - 1,
+                LotMethod::Hifo => (0..lots.len())
+                    .max_by(|a, b| lots[*a].price.total_cmp(&lots[*b].price))
+                    .unwrap(),
+            };
+            let lot = &mut lots[index];
+            let matched = lot.volume.min(remaining);
+            matches.push(LotMatch {
+                acquired: Some(lot.time),
+                volume: matched,
+                cost: matched * lot.price,
+                proceeds: matched * net_price,
+                gain: matched * (net_price - lot.price),
+            });
+            lot.volume -= matched;
+            remaining -= matched;
+            if lot.volume <= VOLUME_EPSILON {
+                lots.remove(index);
+            }
+        }
+        if remaining > VOLUME_EPSILON {
+            matches.push(LotMatch {
+                acquired: None,
+                volume: remaining,
+                cost: 0.0,
+                proceeds: remaining * net_price,
+                gain: remaining * net_price,
+            });
+        }
+        matches
+    }
+
+    pub fn volume(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.volume).sum()
+    }
+
+    /// Returns the cost of the volume still held.
+    pub fn cost(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.volume * lot.price).sum()
+    }
+}
+
+/// Part of a sale matched against a single lot.
+#[derive(Serialize, Debug, Clone)]
+pub struct LotMatch {
+    pub acquired: Option<u64>, // None if the volume had no known lot
+    pub volume: f64,
+    pub cost: f64,     // Including the buy fee
+    pub proceeds: f64, // Net of the sell fee
+    pub gain: f64,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Trade {
+    pub time: u64,
+    pub pair: String,
+    pub side: String,
+    pub volume: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub matches: Vec<LotMatch>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct YearGains {
+    pub proceeds: f64,
+    pub cost: f64,
+    pub gain: f64,
+}
+
+/// Every execution with the lots it opened or closed. Holdings at the first priced balance
+/// snapshot are opened as lots at their price then, and fills before it are left out since
+/// those holdings already include them.
+pub struct Ledger {
+    method: LotMethod,
+    lots: HashMap<String, Lots>, // By asset
+    trades: Vec<Trade>,
+    started: bool,
+}
+
+impl Ledger {
+    pub fn new(method: LotMethod) -> Self {
+        Ledger {
+            method,
+            lots: HashMap::new(),
+            trades: Vec::new(),
+            started: false,
+        }
+    }
+
+    pub fn from_entries(method: LotMethod, entries: &[Entry]) -> Self {
+        let mut ledger = Ledger::new(method);
+        for entry in entries.iter() {
+            ledger.apply(entry);
+        }
+        ledger
+    }
+
+    pub fn apply(&mut self, entry: &Entry) {
+        match &entry.event {
+            Event::Balances { assets } => {
+                if self.started || assets.values().any(|(_, price)| *price == 0.0) {
+                    return;
+                }
+                for (asset, (amount, price)) in assets.iter() {
+                    if asset != "ZUSD" {
+                        self.lots
+                            .entry(asset.clone())
+                            .or_default()
+                            .buy(entry.time, *amount, *price, 0.0);
+                    }
+                }
+                self.started = true;
+            }
+            Event::Fill {
+                pair,
+                side,
+                price,
+                volume,
+                fee,
+                ..
+            } => {
+                if !self.started || *volume <= 0.0 {
+                    return;
+                }
+                let asset = pair.strip_suffix("/USD").unwrap_or(pair).to_string();
+                let lots = self.lots.entry(asset).or_default();
+                let matches = if side == "buy" {
+                    lots.buy(entry.time, *volume, *price, *fee);
+                    vec![]
+                } else {
+                    lots.sell(self.method, *volume, *price, *fee)
+                };
+                self.trades.push(Trade {
+                    time: entry.time,
+                    pair: pair.clone(),
+                    side: side.clone(),
+                    volume: *volume,
+                    price: *price,
+                    fee: *fee,
+                    matches,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub fn get_trades(&self) -> &[Trade] {
+        &self.trades
+    }
+
+    /// Returns realized gains per calendar year of sale (UTC).
+    pub fn gains_by_year(&self) -> BTreeMap<i32, YearGains> {
+        let mut years: BTreeMap<i32, YearGains> = BTreeMap::new();
+        for trade in self.trades.iter() {
+            let gains = years.entry(year(trade.time)).or_default();
+            for lot_match in trade.matches.iter() {
+                gains.proceeds += lot_match.proceeds;
+                gains.cost += lot_match.cost;
+                gains.gain += lot_match.gain;
+            }
+        }
+        years
+    }
+
+    /// Writes one row per buy and per lot matched by a sale. A sale's fee is split over its rows
+    /// by volume, so the fee column sums to the fees paid.
+    pub fn write_trades_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut csv = csv::Writer::from_writer(writer);
+        csv.write_record([
+            "time",
+            "pair",
+            "side",
+            "volume",
+            "price",
+            "fee",
+            "lot_acquired",
+            "lot_volume",
+            "cost",
+            "proceeds",
+            "gain",
+        ])?;
+        for trade in self.trades.iter() {
+            let execution = [
+                format_time(trade.time),
+                trade.pair.clone(),
+                trade.side.clone(),
+                trade.volume.to_string(),
+                trade.price.to_string(),
+            ];
+            if trade.matches.is_empty() {
+                csv.write_record(
+                    execution
+                        .iter()
+                        .cloned()
+                        .chain([trade.fee.to_string()])
+                        .chain(vec![String::new(); 5]),
+                )?;
+            }
+            for lot_match in trade.matches.iter() {
+                let fee = trade.fee * lot_match.volume / trade.volume;
+                let lot = [
+                    fee.to_string(),
+                    lot_match.acquired.map(format_time).unwrap_or_default(),
+                    lot_match.volume.to_string(),
+                    lot_match.cost.to_string(),
+                    lot_match.proceeds.to_string(),
+                    lot_match.gain.to_string(),
+                ];
+                csv.write_record(execution.iter().chain(lot.iter()))?;
+            }
+        }
+        csv.flush()?;
+        Ok(())
+    }
+
+    pub fn write_gains_csv<W: Write>(&self, writer: W) -> Result<(), Box<dyn Error>> {
+        let mut csv = csv::Writer::from_writer(writer);
+        csv.write_record(["year", "proceeds", "cost", "gain"])?;
+        for (year, gains) in self.gains_by_year() {
+            csv.write_record([
+                year.to_string(),
+                gains.proceeds.to_string(),
+                gains.cost.to_string(),
+                gains.gain.to_string(),
+            ])?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+}
+
+fn year(time: u64) -> i32 {
+    DateTime::from_timestamp(time as i64, 0).unwrap().year()
+}
+
+fn format_time(time: u64) -> String {
+    DateTime::from_timestamp(time as i64, 0)
+        .unwrap()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
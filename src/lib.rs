@@ -1,5 +1,29 @@
 pub mod account;
+pub mod api;
+pub mod auth;
+pub mod book;
+pub mod circuit_breaker;
+pub mod cli;
+pub mod clock;
+pub mod config;
+pub mod control;
+pub mod fees;
+pub mod history;
+pub mod journal;
+pub mod keystore;
+pub mod kill_switch;
+pub mod ledger;
+pub mod logging;
 pub mod messages;
+pub mod metrics;
+pub mod paper;
+pub mod pnl;
 pub mod product;
+pub mod recorder;
+pub mod replay;
+pub mod report;
+pub mod risk;
+pub mod strategy;
 pub mod task;
+pub mod volatility;
 pub mod websocket;
@@ -0,0 +1,27 @@
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_LEVEL: &str = "info";
+
+/// Installs the global subscriber.
+///
+/// # Arguments
+///
+/// * `level` - A filter such as "debug" or "rebalancer=debug,info". Falls back to RUST_LOG,
+///   then to info.
+/// * `json` - Emit one JSON object per line instead of human readable output.
+pub fn init(level: Option<&str>, json: bool) {
+    let filter = match level {
+        Some(level) => EnvFilter::new(level),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LEVEL)),
+    };
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    if json {
+        builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .init();
+    } else {
+        builder.init();
+    }
+}
@@ -1,56 +1,570 @@
+use clap::Parser;
 use dotenv::dotenv;
+use futures::future::select_all;
 use rebalancer::account::{Portfolio, Signer};
-use rebalancer::task;
+use rebalancer::api::{self, AppState};
+use rebalancer::auth::{self, Credentials, Role};
+use rebalancer::cli::{Cli, Command, ConfigCommand, GlobalArgs};
+use rebalancer::clock::{SharedClock, SystemClock};
+use rebalancer::config::Config;
+use rebalancer::control::{self, Controls, Markets};
+use rebalancer::fees;
+use rebalancer::history;
+use rebalancer::journal::{self, Event, Journal};
+use rebalancer::keystore;
+use rebalancer::ledger::{Ledger, LotMethod};
+use rebalancer::logging;
+use rebalancer::metrics::METRICS;
+use rebalancer::recorder::{self, Recorder};
+use rebalancer::replay::{self, Summary};
+use rebalancer::report;
+use rebalancer::task::{self, Context};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
 use std::sync::Arc;
+use tokio::net::TcpListener;
 use tokio::signal::ctrl_c;
 use tokio::sync::Mutex;
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+const BALANCE_SNAPSHOT_INTERVAL: u64 = 300; // seconds
+const NO_CREDENTIALS: &str =
+    "No Kraken credentials, set KRAKEN_KEY and KRAKEN_SECRET or KRAKEN_KEYSTORE";
+
+type SharedSigner = Arc<Mutex<Signer>>;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+    let cli = Cli::parse();
+    logging::init(
+        cli.global.log_level.as_deref(),
+        std::env::var("LOG_FORMAT").is_ok_and(|format| format == "json"),
+    );
 
-    let signer = Arc::new(Mutex::new(
-        Signer::new(
-            std::env::var("KRAKEN_KEY").expect("KRAKEN_KEY not set"),
-            std::env::var("KRAKEN_SECRET").expect("KRAKEN_SECRET not set"),
-        )
-        .await,
-    ));
+    let global = cli.global;
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(load_trading_config(&global), &global.journal, false).await,
+        Command::Paper { paper_journal } => {
+            run(load_trading_config(&global), &paper_journal, true).await
+        }
+        Command::Backtest { recordings } => {
+            backtest(load_trading_config(&global), &global.journal, &recordings).await
+        }
+        Command::Balances => print_balances(load_config(&global)).await,
+        Command::Orders => print_orders(load_config(&global)).await,
+        Command::CancelAll => cancel_all(load_config(&global)).await,
+        Command::Rebalance { dry_run } => rebalance(load_config(&global), &global, dry_run).await,
+        Command::Report {
+            json,
+            rebalance_period,
+        } => print_report(&global.journal, json, rebalance_period),
+        Command::ExportTrades { method, out } => export_trades(&global.journal, method, &out),
+        Command::Replay {
+            pair,
+            speed,
+            out,
+            recordings,
+        } => {
+            let config = load_config(&global);
+            run_replay(config, &global.journal, &pair, speed, &out, &recordings).await
+        }
+        Command::Keystore { path } => create_keystore(&path),
+        Command::Config(ConfigCommand::Validate) => validate_config(&global),
+    }
+}
 
-    // {
-    //     let signer = signer.lock().unwrap();
-    //     let token = signer.get_ws_token().await;
-    //     println!("{}", token);
+/// Loads the config, restricted to the pairs given on the command line if any.
+fn load_config(global: &GlobalArgs) -> Config {
+    let mut config = Config::load(&global.config).expect("Failed to load config");
+    if !global.pairs.is_empty() {
+        config.select_pairs(&global.pairs);
+    }
+    if let Some(bind) = &global.api_bind {
+        config.api_bind = bind.clone();
+    }
+    config
+}
 
-    //     let balances = signer.get_account_balances().await;
-    //     println!("{:?}", balances);
-    // }
+/// Loads the config for trading, exiting with an error if it has problems.
+fn load_trading_config(global: &GlobalArgs) -> Config {
+    let config = load_config(global);
+    let problems = config.validate();
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("{}", problem);
+        }
+        std::process::exit(1);
+    }
+    config
+}
 
-    let portfolio = Arc::new(Mutex::new(Portfolio::new(signer.clone()).await));
+/// Returns a signer for the role's credentials, or None if none are set.
+async fn load_signer(role: Role, config: &Config, clock: &SharedClock) -> Option<SharedSigner> {
+    let credentials = Credentials::load(role)
+        .unwrap_or_else(|e| panic!("Invalid {:?} Kraken credentials: {}", role, e))?;
+    let signer = Signer::new(credentials, config.endpoints.rest.clone(), clock.clone()).await;
+    Some(Arc::new(Mutex::new(signer)))
+}
 
-    let eth = "ETH/USD".to_string();
-    let btc = "XBT/USD".to_string(); // bitcoin
-    let sol = "SOL/USD".to_string(); // solana
-    let arb = "ARB/USD".to_string(); // arbitrum
+/// Returns the signer for balances and fees, which uses the read-only key when there is one.
+async fn load_read_signer(config: &Config, clock: &SharedClock) -> Option<SharedSigner> {
+    match load_signer(Role::ReadOnly, config, clock).await {
+        Some(signer) => Some(signer),
+        None => load_signer(Role::Trading, config, clock).await,
+    }
+}
+
+/// Trades the configured pairs until interrupted. Paper trading needs no trading key, orders
+/// are logged instead of sent.
+async fn run(config: Config, journal_path: &str, paper: bool) {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let signer = match paper {
+        true => None,
+        false => Some(
+            load_signer(Role::Trading, &config, &clock)
+                .await
+                .expect(NO_CREDENTIALS),
+        ),
+    };
+    // Balances and fees use the read-only key when there is one
+    let read_signer = match load_signer(Role::ReadOnly, &config, &clock).await {
+        Some(read_signer) => Some(read_signer),
+        None if paper => load_signer(Role::Trading, &config, &clock).await,
+        None => signer.clone(),
+    };
+    if paper {
+        info!(
+            journal = journal_path,
+            "Paper trading, orders won't be sent"
+        );
+    }
+
+    let journal = Arc::new(Mutex::new(
+        Journal::open(journal_path, clock.clone()).expect("Failed to open journal"),
+    ));
+
+    let portfolio = {
+        let mut journal = journal.lock().await;
+        let mut portfolio = match &read_signer {
+            Some(read_signer) => Portfolio::new(read_signer.clone(), clock.clone()).await,
+            None => Portfolio::from_assets(
+                journal
+                    .get_balances()
+                    .expect("Paper trading needs credentials or journaled balances")
+                    .clone(),
+                clock.clone(),
+            ),
+        };
+        if let Some(balances) = journal.get_balances() {
+            portfolio.restore(balances);
+        }
+        journal.append(Event::Balances {
+            assets: portfolio.get_assets(),
+        });
+        Arc::new(Mutex::new(portfolio))
+    };
+    tokio::spawn(snapshot_balances(portfolio.clone(), journal.clone()));
+    if let Some(read_signer) = read_signer {
+        tokio::spawn(fees::refresh(
+            portfolio.clone(),
+            read_signer,
+            config.pairs.iter().map(|p| p.pair.clone()).collect(),
+        ));
+    }
+
+    let mut controls = Controls::new(config.kill_switch.clone());
+    if let Some(reason) = journal.lock().await.get_kill_switch() {
+        warn!(%reason, "Kill switch tripped before restart, trading stays halted");
+        controls.kill_switch.trip(reason.to_string());
+    }
+    let controls = Arc::new(Mutex::new(controls));
+    let markets: Markets = Arc::new(Mutex::new(HashMap::new()));
+    tokio::spawn(control::monitor_kill_switch(
+        portfolio.clone(),
+        controls.clone(),
+        markets.clone(),
+        journal.clone(),
+        clock.clone(),
+    ));
+    let recorder = config
+        .recorder
+        .clone()
+        .map(|config| Arc::new(Mutex::new(Recorder::new(config))));
+    if let Some(recorder) = &recorder {
+        tokio::spawn(recorder::flush_periodically(recorder.clone()));
+    }
+    let state = AppState {
+        portfolio: portfolio.clone(),
+        markets: markets.clone(),
+        controls: controls.clone(),
+        journal: journal.clone(),
+        pairs: config.pairs.iter().map(|p| p.pair.clone()).collect(),
+        token: auth::read_var("API_TOKEN")
+            .expect("Failed to read API_TOKEN")
+            .map(Arc::new),
+    };
+    let listener = TcpListener::bind((config.api_bind.as_str(), config.api_port))
+        .await
+        .unwrap_or_else(|e| {
+            panic!(
+                "Failed to bind API to {}:{}: {}",
+                config.api_bind, config.api_port, e
+            )
+        });
+    tokio::spawn(api::serve(state, listener));
+    let context = Context {
+        portfolio,
+        journal,
+        controls,
+        markets,
+        signer,
+        recorder,
+        endpoints: config.endpoints.clone(),
+        cache_dir: config.cache_dir.clone(),
+        clock,
+    };
 
     loop {
         // Wait a bit for the portfolio to be initialized.
         tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
 
-        let task_eth = task::spawn(eth.clone(), portfolio.clone(), signer.clone()).await;
-        let task_btc = task::spawn(btc.clone(), portfolio.clone(), signer.clone()).await;
-        let task_sol = task::spawn(sol.clone(), portfolio.clone(), signer.clone()).await;
-        let task_arb = task::spawn(arb.clone(), portfolio.clone(), signer.clone()).await;
-
-        let _ = tokio::select! {
-            _ = task_eth => (),
-            _ = task_btc => (),
-            _ = task_sol => (),
-            _ = task_arb => (),
-            _ = ctrl_c() => (), // Graceful shutdown
+        let mut tasks = Vec::new();
+        for pair_config in config.pairs.iter() {
+            tasks.push(task::spawn(pair_config.clone(), context.clone()).await);
+        }
+
+        tokio::select! {
+            _ = select_all(tasks) => (),
+            _ = ctrl_c() => break, // Graceful shutdown
         };
-        println!("Restarting...");
+        warn!("Restarting...");
+        METRICS.ws_reconnects.inc();
+    }
+    if let Some(recorder) = &context.recorder {
+        recorder.lock().await.finish();
+    }
+    info!("Exiting...");
+}
+
+/// Periodically journals the portfolio so performance can be reconstructed later.
+async fn snapshot_balances(portfolio: Arc<Mutex<Portfolio>>, journal: Arc<Mutex<Journal>>) {
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(BALANCE_SNAPSHOT_INTERVAL));
+    interval.tick().await; // The first tick completes immediately
+    loop {
+        interval.tick().await;
+        let assets = { portfolio.lock().await.get_assets() };
+        journal.lock().await.append(Event::Balances { assets });
+    }
+}
+
+/// Writes trades.csv and gains.csv from the journal's fills.
+fn export_trades(journal_path: &str, method: LotMethod, out: &str) {
+    let out = Path::new(out);
+    let entries = journal::read_entries(journal_path).expect("Failed to read journal");
+    let ledger = Ledger::from_entries(method, &entries);
+    ledger
+        .write_trades_csv(File::create(out.join("trades.csv")).unwrap())
+        .expect("Failed to write trades");
+    ledger
+        .write_gains_csv(File::create(out.join("gains.csv")).unwrap())
+        .expect("Failed to write gains");
+    info!(
+        trades = ledger.get_trades().len(),
+        ?method,
+        out = %out.display(),
+        "Exported trades"
+    );
+}
+
+/// Prints performance against the HODL and periodic rebalance benchmarks.
+fn print_report(journal_path: &str, json: bool, rebalance_period: u64) {
+    let entries = journal::read_entries(journal_path).expect("Failed to read journal");
+    match report::generate(&entries, rebalance_period) {
+        Some(report) if json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        Some(report) => report.print_table(),
+        None => warn!("Not enough priced balance snapshots in the journal for a report"),
+    }
+}
+
+/// Replays recordings through a market for one pair and writes the messages it would have
+/// sent as JSONL. The portfolio starts from the journaled balances at the recording's start.
+async fn run_replay(
+    config: Config,
+    journal_path: &str,
+    pair: &str,
+    speed: Option<f64>,
+    out: &str,
+    recordings: &[String],
+) {
+    let pair_config = config
+        .pairs
+        .iter()
+        .find(|p| p.pair == pair)
+        .unwrap_or_else(|| panic!("{} is not configured", pair))
+        .clone();
+    let frames = replay::read_frames(recordings).expect("Failed to read recordings");
+    let assets = get_journaled_assets(journal_path, &frames);
+
+    let captured = replay::run(pair_config, &frames, assets, config.kill_switch, speed).await;
+    let mut file = BufWriter::new(File::create(out).unwrap());
+    for message in captured.iter() {
+        writeln!(file, "{}", serde_json::to_string(message).unwrap()).unwrap();
+    }
+    file.flush().unwrap();
+    info!(messages = captured.len(), out, "Replay finished");
+}
+
+/// Replays recordings through every pair's market without waiting and prints the orders
+/// each would have placed. Orders aren't filled, so the portfolio stays as journaled.
+async fn backtest(config: Config, journal_path: &str, recordings: &[String]) {
+    let frames = replay::read_frames(recordings).expect("Failed to read recordings");
+    let assets = get_journaled_assets(journal_path, &frames);
+
+    println!(
+        "{:<12}{:>8}{:>8}{:>14}{:>14}{:>10}",
+        "Pair", "Buys", "Sells", "Bought USD", "Sold USD", "Cancels"
+    );
+    for pair_config in config.pairs.iter() {
+        let pair = pair_config.pair.clone();
+        let captured = replay::run(
+            pair_config.clone(),
+            &frames,
+            assets.clone(),
+            config.kill_switch.clone(),
+            None,
+        )
+        .await;
+        let summary = Summary::from_captured(&captured);
+        println!(
+            "{:<12}{:>8}{:>8}{:>14.2}{:>14.2}{:>10}",
+            pair,
+            summary.buys,
+            summary.sells,
+            summary.bought_usd,
+            summary.sold_usd,
+            summary.cancels
+        );
+    }
+}
+
+/// Returns the last journaled balances from before the recordings start.
+fn get_journaled_assets(
+    journal_path: &str,
+    frames: &[rebalancer::recorder::Frame],
+) -> HashMap<String, (f64, f64)> {
+    let start = frames.first().map(|frame| frame.time as u64).unwrap_or(0);
+    let entries = journal::read_entries(journal_path).expect("Failed to read journal");
+    entries
+        .iter()
+        .filter_map(|entry| match &entry.event {
+            Event::Balances { assets } => Some((entry.time, assets)),
+            _ => None,
+        })
+        .fold(None, |found, (time, assets)| match found {
+            Some(_) if time > start => found,
+            _ => Some(assets.clone()),
+        })
+        .unwrap_or_else(|| {
+            warn!("No journaled balances, replaying with an empty portfolio");
+            HashMap::new()
+        })
+}
+
+/// Returns the account's portfolio with each configured pair priced at its last close.
+async fn get_priced_portfolio(config: &Config) -> Portfolio {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let signer = load_read_signer(config, &clock)
+        .await
+        .expect(NO_CREDENTIALS);
+    let mut portfolio = Portfolio::new(signer, clock.clone()).await;
+    for pair in config.pairs.iter() {
+        let history = history::load(
+            &config.endpoints.rest,
+            &config.cache_dir,
+            &pair.pair,
+            clock.as_ref(),
+        )
+        .await;
+        match history.bars.last() {
+            Some(bar) => portfolio.set_pair_price(pair.pair.clone(), bar.close),
+            None => warn!(pair = %pair.pair, "No price"),
+        }
+    }
+    portfolio
+}
+
+/// Prints each asset's balance, value and share of the portfolio.
+async fn print_balances(config: Config) {
+    let portfolio = get_priced_portfolio(&config).await;
+    let mut assets: Vec<_> = portfolio.get_assets().into_iter().collect();
+    assets.sort_by(|a, b| a.0.cmp(&b.0));
+    println!(
+        "{:<8}{:>16}{:>14}{:>14}{:>10}",
+        "Asset", "Amount", "Price", "Value", "Weight"
+    );
+    for (asset, (amount, price)) in assets {
+        println!(
+            "{:<8}{:>16.8}{:>14.4}{:>14.2}{:>9.2}%",
+            asset,
+            amount,
+            price,
+            amount * price,
+            portfolio.get_asset_allocation(asset.clone()) * 100.0
+        );
+    }
+    println!("Total value {:.2}", portfolio.get_total_value());
+}
+
+/// Prints the account's open orders.
+async fn print_orders(config: Config) {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let signer = load_read_signer(&config, &clock)
+        .await
+        .expect(NO_CREDENTIALS);
+    let orders = signer.lock().await.get_open_orders().await;
+    let mut orders: Vec<_> = orders
+        .as_object()
+        .cloned()
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    orders.sort_by(|a, b| {
+        a.1["descr"]["pair"]
+            .to_string()
+            .cmp(&b.1["descr"]["pair"].to_string())
+    });
+    println!(
+        "{:<22}{:<10}{:<6}{:>14}{:>16}",
+        "Order", "Pair", "Side", "Price", "Volume"
+    );
+    for (txid, order) in orders {
+        let descr = &order["descr"];
+        println!(
+            "{:<22}{:<10}{:<6}{:>14}{:>16}",
+            txid,
+            descr["pair"].as_str().unwrap_or_default(),
+            descr["type"].as_str().unwrap_or_default(),
+            descr["price"].as_str().unwrap_or_default(),
+            order["vol"].as_str().unwrap_or_default()
+        );
+    }
+}
+
+/// Cancels every open order on the account, including ones the bot didn't place.
+async fn cancel_all(config: Config) {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let signer = load_signer(Role::Trading, &config, &clock)
+        .await
+        .expect(NO_CREDENTIALS);
+    let count = signer
+        .lock()
+        .await
+        .cancel_all_orders()
+        .await
+        .expect("Failed to cancel orders");
+    info!(count, "Cancelled all orders");
+}
+
+/// Asks the running instance to rebalance, or with `dry_run` prints the trades that would
+/// bring each configured asset to its target weight.
+async fn rebalance(config: Config, global: &GlobalArgs, dry_run: bool) {
+    if !dry_run {
+        let base = config.api_url();
+        let urls: Vec<String> = match global.pairs.is_empty() {
+            true => vec![format!("{}/rebalance", base)],
+            false => global
+                .pairs
+                .iter()
+                .map(|pair| format!("{}/pairs/{}/rebalance", base, pair.replace('/', "-")))
+                .collect(),
+        };
+        let token = auth::read_var("API_TOKEN")
+            .expect("Failed to read API_TOKEN")
+            .expect("API_TOKEN isn't set, it's needed to use the control routes");
+        let client = reqwest::Client::new();
+        for url in urls {
+            let response = client
+                .post(&url)
+                .bearer_auth(token.as_str())
+                .send()
+                .await
+                .unwrap_or_else(|e| panic!("Failed to reach the running instance: {}", e));
+            if !response.status().is_success() {
+                eprintln!("Rebalance request to {} failed: {}", url, response.status());
+                std::process::exit(1);
+            }
+            info!(url, status = %response.status(), "Requested rebalance");
+        }
+        return;
+    }
+
+    let portfolio = get_priced_portfolio(&config).await;
+    if !portfolio.has_prices() {
+        warn!("Some assets have no price, only configured pairs are priced");
+    }
+    let assets = portfolio.get_assets();
+    let target = portfolio.get_total_value() / assets.len() as f64;
+    println!(
+        "{:<10}{:>14}{:>14}{:<6}{:>16}{:>14}",
+        "Pair", "Value", "Target", " Side", "Volume", "Notional"
+    );
+    for pair in config.pairs.iter() {
+        let (amount, price) = portfolio.get_pair(pair.pair.clone());
+        if price == 0.0 {
+            continue;
+        }
+        let delta = target - amount * price;
+        let side = if delta > 0.0 { "buy" } else { "sell" };
+        println!(
+            "{:<10}{:>14.2}{:>14.2} {:<5}{:>16.8}{:>14.2}",
+            pair.pair,
+            amount * price,
+            target,
+            side,
+            delta.abs() / price,
+            delta.abs()
+        );
+    }
+}
+
+/// Encrypts an API key pair, prompted for without echo, into a new keystore.
+fn create_keystore(path: &str) {
+    let key = Zeroizing::new(rpassword::prompt_password("API key: ").unwrap());
+    let secret = Zeroizing::new(rpassword::prompt_password("API secret: ").unwrap());
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Passphrase: ").unwrap());
+    let confirmation = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ").unwrap());
+    if passphrase != confirmation {
+        panic!("Passphrases don't match");
+    }
+    keystore::create(path, &key, &secret, &passphrase)
+        .unwrap_or_else(|e| panic!("Failed to create keystore: {}", e));
+    info!(path, "Created keystore");
+}
+
+/// Checks the config file, exiting with an error if it's missing or invalid.
+fn validate_config(global: &GlobalArgs) {
+    if !Path::new(&global.config).exists() {
+        eprintln!("{} not found", global.config);
+        std::process::exit(1);
+    }
+    let config = match Config::load(&global.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}: {}", global.config, e);
+            std::process::exit(1);
+        }
+    };
+    let problems = config.validate();
+    if !problems.is_empty() {
+        for problem in problems.iter() {
+            eprintln!("{}", problem);
+        }
+        std::process::exit(1);
     }
-    println!("Exiting...");
-    // let _ = tokio::join!(task1, task2);
+    println!("{} is valid, {} pairs", global.config, config.pairs.len());
 }
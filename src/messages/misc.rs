@@ -3,9 +3,9 @@ use std::collections::HashMap;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SystemStatus {
-    event: String,
-    status: String,
-    version: String,
+    pub event: String,
+    pub status: String, // online, maintenance, cancel_only or post_only
+    pub version: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -19,6 +19,17 @@ pub struct SubscriptionStatus {
     subscription: HashMap<String, String>,
 }
 
+/// Response to a request such as addOrder or cancelOrder.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RequestStatus {
+    pub event: String,
+    pub status: String,
+    pub txid: Option<String>,
+    pub descr: Option<String>,
+    #[serde(rename = "errorMessage")]
+    pub error_message: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Heartbeat {
     event: String,
@@ -2,9 +2,9 @@ use serde::{Deserialize, Serialize};
 
 mod public;
 pub use public::*;
-mod private;
-pub use private::*;
 mod open_orders;
+#[allow(dead_code)] // ownTrades isn't subscribed to yet
+mod private;
 pub use open_orders::*;
 mod misc;
 pub use misc::*;
@@ -13,9 +13,11 @@ pub use misc::*;
 #[serde(untagged)]
 pub enum WSPayload {
     PublicMessage(PublicMessage),
+    BookUpdate(BookUpdateMessage),
     OpenOrders(OpenOrders),
     // OwnTrades(OwnTradesData),
     SystemStatus(SystemStatus),
     SubscriptionStatus(SubscriptionStatus),
+    RequestStatus(RequestStatus),
     Heartbeat(Heartbeat),
 }
@@ -27,10 +27,27 @@ pub struct TickerData {
 //     // o: Vec<serde_json::Value>,
 // }
 
+/// Snapshot (`as`/`bs`) or update (`a`/`b`) from the book channel. Levels are
+/// [price, volume, timestamp] and updates may carry a trailing "r" for republished levels.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BookData {
+    #[serde(rename = "as")]
+    pub snapshot_asks: Option<Vec<Vec<String>>>,
+    #[serde(rename = "bs")]
+    pub snapshot_bids: Option<Vec<Vec<String>>>,
+    #[serde(rename = "a")]
+    pub asks: Option<Vec<Vec<String>>>,
+    #[serde(rename = "b")]
+    pub bids: Option<Vec<Vec<String>>>,
+    #[serde(rename = "c")]
+    pub checksum: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(untagged)]
 pub enum PublicData {
     Ticker(TickerData),
+    Book(BookData),
     OHLC(Vec<serde_json::Value>),
 }
 
@@ -41,3 +58,13 @@ pub struct PublicMessage {
     pub channel_name: String,
     pub pair: String,
 }
+
+/// Book update carrying both ask and bid changes, sent as two separate objects.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BookUpdateMessage(
+    pub i64,      // channel_id
+    pub BookData, // asks
+    pub BookData, // bids
+    pub String,   // channel_name
+    pub String,   // pair
+);
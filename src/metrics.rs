@@ -0,0 +1,220 @@
+use prometheus::{
+    CounterVec, Encoder, Gauge, GaugeVec, HistogramOpts, HistogramVec, IntCounter, IntCounterVec,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Instant;
+
+/// Process wide metrics, exported on `/metrics`.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+
+    // Portfolio, set when scraped
+    pub asset_value: GaugeVec,
+    pub asset_weight: GaugeVec,
+    pub asset_target_delta: GaugeVec,
+    pub portfolio_value: Gauge,
+    pub open_orders: IntGaugeVec,
+
+    // Orders
+    pub orders_placed: IntCounterVec,
+    pub orders_canceled: IntCounterVec,
+    pub orders_filled: IntCounterVec,
+    pub orders_rejected: IntCounterVec,
+    pub risk_rejections: IntCounterVec,
+    pub fees_paid: CounterVec,
+    pub fee_rate: GaugeVec,
+
+    // Connectivity
+    pub ws_reconnects: IntCounter,
+    pub circuit_breaker_trips: IntCounterVec,
+    pub parse_errors: IntCounterVec,
+    pub rest_latency: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let metrics = Metrics {
+            registry: Registry::new(),
+            asset_value: GaugeVec::new(
+                Opts::new("rebalancer_asset_value_usd", "Value of each asset in USD"),
+                &["asset"],
+            )
+            .unwrap(),
+            asset_weight: GaugeVec::new(
+                Opts::new(
+                    "rebalancer_asset_weight",
+                    "Share of the portfolio held in each asset",
+                ),
+                &["asset"],
+            )
+            .unwrap(),
+            asset_target_delta: GaugeVec::new(
+                Opts::new(
+                    "rebalancer_asset_target_delta_percent",
+                    "Distance of each asset from its target weight, in percentage",
+                ),
+                &["asset"],
+            )
+            .unwrap(),
+            portfolio_value: Gauge::new(
+                "rebalancer_portfolio_value_usd",
+                "Total portfolio value in USD",
+            )
+            .unwrap(),
+            open_orders: IntGaugeVec::new(
+                Opts::new("rebalancer_open_orders", "Live orders per pair and side"),
+                &["pair", "side"],
+            )
+            .unwrap(),
+            orders_placed: IntCounterVec::new(
+                Opts::new(
+                    "rebalancer_orders_placed_total",
+                    "Orders sent to the exchange",
+                ),
+                &["pair", "side"],
+            )
+            .unwrap(),
+            orders_canceled: IntCounterVec::new(
+                Opts::new("rebalancer_orders_canceled_total", "Orders canceled"),
+                &["pair"],
+            )
+            .unwrap(),
+            orders_filled: IntCounterVec::new(
+                Opts::new("rebalancer_orders_filled_total", "Orders filled"),
+                &["pair", "side"],
+            )
+            .unwrap(),
+            orders_rejected: IntCounterVec::new(
+                Opts::new(
+                    "rebalancer_orders_rejected_total",
+                    "Orders rejected by the exchange",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            risk_rejections: IntCounterVec::new(
+                Opts::new(
+                    "rebalancer_risk_rejections_total",
+                    "Quotes rejected by the pre-trade risk checks",
+                ),
+                &["pair", "reason"],
+            )
+            .unwrap(),
+            fees_paid: CounterVec::new(
+                Opts::new(
+                    "rebalancer_fees_paid_usd_total",
+                    "Fees paid on fills in USD",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            fee_rate: GaugeVec::new(
+                Opts::new(
+                    "rebalancer_fee_rate",
+                    "Current fee rate per pair, as a fraction of notional",
+                ),
+                &["pair", "liquidity"],
+            )
+            .unwrap(),
+            ws_reconnects: IntCounter::new(
+                "rebalancer_ws_reconnects_total",
+                "Times the WebSocket connections were restarted",
+            )
+            .unwrap(),
+            circuit_breaker_trips: IntCounterVec::new(
+                Opts::new(
+                    "rebalancer_circuit_breaker_trips_total",
+                    "Times quoting was paused on unhealthy market data",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            parse_errors: IntCounterVec::new(
+                Opts::new(
+                    "rebalancer_message_parse_errors_total",
+                    "WebSocket messages that failed to parse",
+                ),
+                &["pair"],
+            )
+            .unwrap(),
+            rest_latency: HistogramVec::new(
+                HistogramOpts::new(
+                    "rebalancer_rest_latency_seconds",
+                    "Latency of REST API requests",
+                ),
+                &["endpoint"],
+            )
+            .unwrap(),
+        };
+
+        let registry = &metrics.registry;
+        registry
+            .register(Box::new(metrics.asset_value.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.asset_weight.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.asset_target_delta.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.portfolio_value.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.open_orders.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.orders_placed.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.orders_canceled.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.orders_filled.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.orders_rejected.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.risk_rejections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.fees_paid.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.fee_rate.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.ws_reconnects.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.circuit_breaker_trips.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.parse_errors.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(metrics.rest_latency.clone()))
+            .unwrap();
+        metrics
+    }
+
+    /// Records the time since `start` as the latency of a REST request.
+    pub fn observe_rest(&self, endpoint: &str, start: Instant) {
+        self.rest_latency
+            .with_label_values(&[endpoint])
+            .observe(start.elapsed().as_secs_f64());
+    }
+
+    /// Returns all metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
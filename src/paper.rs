@@ -0,0 +1,154 @@
+use crate::product::Market;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+const FILL_CHECK_INTERVAL: u64 = 1; // seconds
+
+/// Stands in for the private connection of a paper trading market. Orders the market sends are
+/// logged and acknowledged as if the exchange had opened them, so the market tracks and
+/// cancels them like live orders. An order fills in full at its limit price, paying the maker
+/// fee, once the ticker trades through it: the ask drops below a bid or the bid rises above an
+/// ask. Queue position and partial fills aren't simulated.
+pub async fn run(mut captured: UnboundedReceiver<String>, market: Arc<Mutex<Market>>) {
+    let mut next_txid = 1;
+    let mut sequence = 0;
+    let mut open: HashMap<String, Value> = HashMap::new(); // txid -> addOrder request
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(FILL_CHECK_INTERVAL));
+    loop {
+        let message = tokio::select! {
+            message = captured.recv() => match message {
+                Some(message) => message,
+                None => break,
+            },
+            _ = interval.tick() => {
+                fill_crossed(&mut open, &mut sequence, &market).await;
+                continue;
+            }
+        };
+        let request: Value = match serde_json::from_str(&message) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!(error = %e, message, "Failed to parse paper request");
+                continue;
+            }
+        };
+        let replies = match request["event"].as_str().unwrap_or_default() {
+            "addOrder" => {
+                let txid = format!("PAPER-{:06}", next_txid);
+                next_txid += 1;
+                info!(
+                    %txid,
+                    side = %request["type"],
+                    price = %request["price"],
+                    volume = %request["volume"],
+                    "Paper order"
+                );
+                let descr = format!(
+                    "{} {} {} @ limit {}",
+                    request["type"].as_str().unwrap_or_default(),
+                    request["volume"].as_str().unwrap_or_default(),
+                    request["pair"].as_str().unwrap_or_default(),
+                    request["price"].as_str().unwrap_or_default()
+                );
+                let order = json!({
+                    "status": "open",
+                    "descr": {
+                        "pair": request["pair"],
+                        "type": request["type"],
+                        "ordertype": "limit",
+                        "price": request["price"],
+                        "price2": "0.00000",
+                        "order": descr,
+                    },
+                    "vol": request["volume"],
+                    "vol_exec": "0.00000000",
+                });
+                open.insert(txid.clone(), request);
+                vec![
+                    json!({ "event": "addOrderStatus", "status": "ok", "txid": txid }),
+                    order_update(&mut sequence, &txid, order),
+                ]
+            }
+            "cancelOrder" => {
+                let mut replies = Vec::new();
+                for txid in request["txid"].as_array().cloned().unwrap_or_default() {
+                    let txid = txid.as_str().unwrap_or_default();
+                    info!(%txid, "Paper cancel");
+                    open.remove(txid);
+                    replies.push(json!({ "event": "cancelOrderStatus", "status": "ok" }));
+                    replies.push(order_update(
+                        &mut sequence,
+                        txid,
+                        json!({ "status": "canceled" }),
+                    ));
+                }
+                replies
+            }
+            _ => vec![],
+        };
+
+        let mut market = market.lock().await;
+        for reply in replies {
+            market.on_message(reply.to_string()).await;
+        }
+    }
+}
+
+/// Fills the open orders the market has traded through.
+async fn fill_crossed(
+    open: &mut HashMap<String, Value>,
+    sequence: &mut i64,
+    market: &Arc<Mutex<Market>>,
+) {
+    let mut market = market.lock().await;
+    let (bid, ask) = match market.get_touch() {
+        Some(touch) => touch,
+        None => return,
+    };
+    let crossed: Vec<String> = open
+        .iter()
+        .filter(|(_, request)| {
+            let price = parse(&request["price"]);
+            match request["type"].as_str().unwrap_or_default() {
+                "buy" => ask < price,
+                "sell" => bid > price,
+                _ => false,
+            }
+        })
+        .map(|(txid, _)| txid.clone())
+        .collect();
+    if crossed.is_empty() {
+        return;
+    }
+
+    let maker = market.get_fees().await.maker;
+    for txid in crossed {
+        let request = open.remove(&txid).unwrap();
+        let (price, volume) = (parse(&request["price"]), parse(&request["volume"]));
+        let cost = price * volume;
+        info!(%txid, side = %request["type"], price, volume, "Paper fill");
+        let order = json!({
+            "status": "closed",
+            "vol_exec": volume.to_string(),
+            "cost": cost.to_string(),
+            "fee": (cost * maker).to_string(),
+            "avg_price": price.to_string(),
+        });
+        let update = order_update(sequence, &txid, order);
+        market.on_message(update.to_string()).await;
+    }
+}
+
+fn parse(value: &Value) -> f64 {
+    value.as_str().unwrap_or_default().parse().unwrap_or(0.0)
+}
+
+/// Returns an openOrders message updating a single order.
+fn order_update(sequence: &mut i64, txid: &str, order: Value) -> Value {
+    *sequence += 1;
+    json!([[{ txid: order }], "openOrders", { "sequence": sequence }])
+}
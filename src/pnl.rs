@@ -0,0 +1,164 @@
+use crate::ledger::{LotMethod, Lots};
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Default, Clone)]
+struct Position {
+    lots: Lots,
+    amount: f64,
+    average_cost: f64, // Including buy fees
+    realized_fifo: f64,
+    realized_average: f64,
+    fees: f64, // In USD
+}
+
+impl Position {
+    fn buy(&mut self, time: u64, volume: f64, price: f64, fee: f64) {
+        let amount = self.amount + volume;
+        if amount > 0.0 {
+            self.average_cost = (self.average_cost * self.amount + price * volume + fee) / amount;
+        }
+        self.amount = amount;
+        self.lots.buy(time, volume, price, fee);
+    }
+
+    fn sell(&mut self, volume: f64, price: f64, fee: f64) {
+        // Volume without a known cost basis, e.g. more than tracked, realizes nothing
+        let covered = volume.min(self.amount.max(0.0));
+        if covered > 0.0 {
+            let net_price = price - fee / volume;
+            self.realized_average += (net_price - self.average_cost) * covered;
+        }
+        self.amount -= volume;
+
+        for lot_match in self.lots.sell(LotMethod::Fifo, volume, price, fee) {
+            if lot_match.acquired.is_some() {
+                self.realized_fifo += lot_match.gain;
+            }
+        }
+    }
+}
+
+/// Cost basis and profit per asset, built from fills with the ledger's lots so it agrees with
+/// the FIFO export. Holdings when tracking starts are taken at their price at that time and
+/// kept as the buy-and-hold benchmark.
+#[derive(Debug, Default)]
+pub struct Pnl {
+    since: Option<u64>,
+    initial: HashMap<String, (f64, f64)>, // (amount, price)
+    positions: HashMap<String, Position>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct AssetPnl {
+    pub asset: String,
+    pub amount: f64,
+    pub average_cost: f64,
+    pub fifo_cost: f64, // Cost of the remaining lots
+    pub realized_fifo: f64,
+    pub realized_average: f64,
+    pub unrealized: f64, // Against the remaining lots
+    pub fees: f64,       // Already included in the costs and realized profit
+}
+
+#[derive(Serialize, Debug)]
+pub struct PnlReport {
+    pub since: Option<u64>,
+    pub assets: Vec<AssetPnl>,
+    pub realized: f64,
+    pub unrealized: f64,
+    pub fees: f64,
+    pub total: f64, // realized + unrealized, net of fees
+    pub value: f64,
+    pub hodl_value: f64, // The starting holdings at current prices
+    pub vs_hodl: f64,
+}
+
+impl Pnl {
+    pub fn is_started(&self) -> bool {
+        self.since.is_some()
+    }
+
+    /// Starts tracking from the given holdings, opening a lot for each asset at its price.
+    pub fn start(&mut self, time: u64, assets: &HashMap<String, (f64, f64)>) {
+        self.since = Some(time);
+        self.initial = assets.clone();
+        for (asset, (amount, price)) in assets.iter() {
+            if asset != "ZUSD" && *amount > 0.0 {
+                self.positions
+                    .entry(asset.clone())
+                    .or_default()
+                    .buy(time, *amount, *price, 0.0);
+            }
+        }
+    }
+
+    /// Records a fill. Fills before tracking starts are ignored, as the holdings it starts
+    /// from already include them.
+    pub fn record_fill(
+        &mut self,
+        time: u64,
+        asset: &str,
+        side: &str,
+        volume: f64,
+        price: f64,
+        fee: f64,
+    ) {
+        if !self.is_started() || volume <= 0.0 {
+            return;
+        }
+        let position = self.positions.entry(asset.to_string()).or_default();
+        if side == "buy" {
+            position.buy(time, volume, price, fee);
+        } else {
+            position.sell(volume, price, fee);
+        }
+        position.fees += fee;
+    }
+
+    /// Returns the profit so far, marking holdings at the given (amount, price) per asset.
+    pub fn report(&self, assets: &HashMap<String, (f64, f64)>) -> PnlReport {
+        let mark = |asset: &str| assets.get(asset).map(|(_, price)| *price).unwrap_or(0.0);
+
+        let mut report = PnlReport {
+            since: self.since,
+            assets: Vec::new(),
+            realized: 0.0,
+            unrealized: 0.0,
+            fees: 0.0,
+            total: 0.0,
+            value: assets.values().map(|(amount, price)| amount * price).sum(),
+            hodl_value: self
+                .initial
+                .iter()
+                .map(|(asset, (amount, price))| {
+                    let price = if asset == "ZUSD" { *price } else { mark(asset) };
+                    amount * price
+                })
+                .sum(),
+            vs_hodl: 0.0,
+        };
+        for (asset, position) in self.positions.iter() {
+            let held = position.lots.volume();
+            let fifo_cost = position.lots.cost();
+            let asset_pnl = AssetPnl {
+                asset: asset.clone(),
+                amount: position.amount,
+                average_cost: position.average_cost,
+                fifo_cost,
+                realized_fifo: position.realized_fifo,
+                realized_average: position.realized_average,
+                unrealized: held * mark(asset) - fifo_cost,
+                fees: position.fees,
+            };
+            report.realized += asset_pnl.realized_fifo;
+            report.unrealized += asset_pnl.unrealized;
+            report.fees += asset_pnl.fees;
+            report.assets.push(asset_pnl);
+        }
+        report.assets.sort_by(|a, b| a.asset.cmp(&b.asset));
+        report.total = report.realized + report.unrealized;
+        report.vs_hodl = report.value - report.hodl_value;
+        report
+    }
+}
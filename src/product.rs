@@ -1,36 +1,47 @@
 use crate::account::Portfolio;
-use crate::messages::{OpenOrders, OrderData, PublicData, TickerData, WSPayload};
-use crate::websocket::send;
-use futures_util::stream::SplitSink;
-use serde_json;
+use crate::book::{OrderBook, BOOK_DEPTH};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::clock::SharedClock;
+use crate::config::{OrderConfig, PairConfig};
+use crate::control::Controls;
+use crate::fees::Fees;
+use crate::history::History;
+use crate::journal::{Event, Journal, MarketState};
+use crate::messages::{
+    BookData, OpenOrders, OrderData, PublicData, RequestStatus, SystemStatus, TickerData, WSPayload,
+};
+use crate::metrics::METRICS;
+use crate::risk::{RiskContext, RiskLimits};
+use crate::strategy::{self, Ladder, MarketSnapshot, PortfolioSnapshot, Quote, Side, Strategy};
+use crate::volatility::{Bar, Volatility};
+use crate::websocket::{send, Sender};
+use serde::Serialize;
 use serde_json::json;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::time;
-use tokio::net::TcpStream;
 use tokio::sync::Mutex;
-use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
-
-type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+use tracing::{debug, info, info_span, warn, Span};
 
 const BUFFER_SIZE: usize = 100; // Number of prices/spreads to keep in memory
 const PRICE_RECORD_INTERVAL: u64 = 10; // seconds
-const ORDER_SIZE_USD: f64 = 30.0;
-const RISK_AVERSION: f64 = 15.0;
-const FEE: f64 = 0.0005;
 const UPDATE_PRICE_THRESHOLD: f64 = 0.0005;
-const BASE_VOLATILITY: f64 = 0.0005;
-const ORDER_CREATION_COOLDOWN: u64 = 300; // seconds
-const DELTA_THRESHOLD: f64 = 1.5;
+const POST_ONLY_RETRY_DELAY: u64 = 10; // seconds
 
 // Ratio for how much mid price updates
-const PRICE_UPDATE_NUMERATOR: f64 = 3.0;
-const PRICE_UPDATE_DENOMINATOR: f64 = 4.0;
-const MIN_SPREAD: f64 = 4.0 * FEE * PRICE_UPDATE_NUMERATOR / PRICE_UPDATE_DENOMINATOR;
+pub const PRICE_UPDATE_NUMERATOR: f64 = 3.0;
+pub const PRICE_UPDATE_DENOMINATOR: f64 = 4.0;
 
 const DECIMALS: u8 = 99;
 
+/// A live order as reported by the exchange.
+#[derive(Serialize, Debug)]
+pub struct OrderSummary {
+    pub order_id: String,
+    pub side: String,
+    pub price: f64,
+    pub volume: f64,
+}
+
 pub struct Market {
     // Constants
     pair: String,
@@ -44,29 +55,56 @@ pub struct Market {
     spreads: VecDeque<f64>,
     spreads_last_updated: u64,
     vol_24hr: f64,
+    touch: Option<(f64, f64)>, // Best bid and ask from the last ticker
+    book: OrderBook,
+    volatility: Volatility,
+    current_bar: Option<Bar>,
+    circuit_breaker: CircuitBreaker,
+    unhealthy: Option<String>, // Why market data can't be trusted right now
 
     // Orders
     bid_orders: HashMap<String, OrderData>,
     ask_orders: HashMap<String, OrderData>,
+    pending_cancels: HashSet<String>,
+    strategy: Box<dyn Strategy>,
+    ladder: Ladder,
+    risk: RiskLimits,
+    order_min: f64,      // Smallest volume the exchange accepts, 0 until known
+    order_cooldown: u64, // seconds
+    order_config: OrderConfig,
 
     // Misc
+    clock: SharedClock,
+    exchange_status: String, // As last reported by systemStatus
     portfolio: Arc<Mutex<Portfolio>>,
-    priv_sink: SplitSink<Socket, Message>,
+    journal: Arc<Mutex<Journal>>,
+    controls: Arc<Mutex<Controls>>,
+    pub_sink: Sender,
+    priv_sink: Sender,
     token: String, // Access token
+    span: Span,    // Carries the pair for calls from outside the market's task
 
     // To prevent multiple orders from being placed at the same time
     last_order_time: u64,
 }
 
 impl Market {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        pair: String,
+        config: PairConfig,
         portfolio: Arc<Mutex<Portfolio>>,
-        priv_sink: SplitSink<Socket, Message>,
+        journal: Arc<Mutex<Journal>>,
+        controls: Arc<Mutex<Controls>>,
+        pub_sink: Sender,
+        priv_sink: Sender,
         token: String,
+        clock: SharedClock,
     ) -> Self {
+        let strategy = strategy::from_config(&config.strategy);
+        let pair = config.pair.clone();
+        info!(pair = %config.pair, strategy = strategy.name(), "Using strategy");
         Market {
-            pair,
+            pair: config.pair,
             decimals: DECIMALS,
             mid_price: 0.0,
             last_price: 0.0,
@@ -75,18 +113,111 @@ impl Market {
             spreads: VecDeque::with_capacity(BUFFER_SIZE),
             spreads_last_updated: 0,
             vol_24hr: 0.0,
+            touch: None,
+            book: OrderBook::new(),
+            volatility: Volatility::new(&config.volatility),
+            current_bar: None,
+            circuit_breaker: CircuitBreaker::new(config.circuit_breaker),
+            unhealthy: None,
 
             bid_orders: HashMap::new(),
             ask_orders: HashMap::new(),
-
+            pending_cancels: HashSet::new(),
+            strategy,
+            ladder: Ladder::new(config.ladder),
+            risk: RiskLimits::new(config.risk),
+            order_min: 0.0,
+            order_cooldown: config.order_cooldown,
+            order_config: config.orders,
+
+            clock,
+            exchange_status: "online".to_string(),
             portfolio,
+            journal,
+            controls,
+            pub_sink,
             priv_sink,
             token,
+            span: info_span!("market", pair = %pair),
 
             last_order_time: 0,
         }
     }
 
+    /// Backfills the price and spread buffers from recent history so strategies have valid
+    /// statistics before the first live ticks. Prices are 1 minute closes rather than samples
+    /// every PRICE_RECORD_INTERVAL, which the estimators account for by their timestamps.
+    pub async fn warm_start(&mut self, history: History) {
+        self.order_min = history.order_min;
+        let skip = history.bars.len().saturating_sub(BUFFER_SIZE);
+        for bar in history.bars.iter() {
+            self.volatility.record_bar(*bar);
+        }
+        for bar in history.bars.iter().skip(skip) {
+            self.volatility.record_price(bar.time, bar.close);
+            self.prices.push_back(bar.close);
+        }
+
+        let mut last_recorded = 0.0;
+        for (time, bid, ask) in history.spreads.iter() {
+            if time - last_recorded >= PRICE_RECORD_INTERVAL as f64 {
+                self.spreads.push_back(2.0 * (ask - bid) / (ask + bid));
+                if self.spreads.len() > BUFFER_SIZE {
+                    self.spreads.pop_front();
+                }
+                last_recorded = *time;
+            }
+        }
+
+        if let Some(price) = self.prices.back().copied() {
+            self.mid_price = price;
+            self.last_price = price;
+            let mut portfolio = self.portfolio.lock().await;
+            portfolio.set_pair_price(self.pair.clone(), price);
+        }
+        info!(
+            prices = self.prices.len(),
+            spreads = self.spreads.len(),
+            order_min = self.order_min,
+            "Warm started"
+        );
+    }
+
+    /// Restores the journaled market and strategy state.
+    pub async fn restore(&mut self) {
+        let state = {
+            self.journal
+                .lock()
+                .await
+                .get_market_state(&self.pair)
+                .cloned()
+        };
+        if let Some(state) = state {
+            info!(
+                last_price = state.last_price,
+                last_order_time = state.last_order_time,
+                "Restoring journaled state"
+            );
+            if state.last_price != 0.0 {
+                self.last_price = state.last_price;
+            }
+            self.last_order_time = state.last_order_time;
+            if let Some(strategy_state) = state.strategy {
+                self.strategy.restore_state(strategy_state);
+            }
+        }
+    }
+
+    async fn journal_state(&self) {
+        let state = MarketState {
+            pair: self.pair.clone(),
+            last_price: self.last_price,
+            last_order_time: self.last_order_time,
+            strategy: self.strategy.get_state(),
+        };
+        self.journal.lock().await.append(Event::MarketState(state));
+    }
+
     pub async fn on_message(&mut self, message: String) {
         let deserialized: Result<WSPayload, serde_json::Error> = serde_json::from_str(&message);
         match deserialized {
@@ -94,22 +225,40 @@ impl Market {
                 WSPayload::PublicMessage(pub_msg) => match pub_msg.data {
                     PublicData::Ticker(data) => self.on_ticker_data(data).await,
                     PublicData::OHLC(data) => {
-                        let price = data[6].as_str().unwrap().parse::<f64>().unwrap();
-                        self.mid_price = price;
-                        self.record_price(price).await;
+                        // [time, etime, open, high, low, close, vwap, volume, count]
+                        let price = data[5].as_str().unwrap().parse::<f64>().unwrap();
+                        let now = self.clock.now_secs();
+                        self.circuit_breaker.on_ohlc(now, price);
+                        let volatility = self.volatility.estimate();
+                        if self
+                            .circuit_breaker
+                            .accept_price(price, self.mid_price, volatility)
+                        {
+                            self.record_bar(&data);
+                            self.mid_price = price;
+                            self.record_price(price).await;
+                        } else {
+                            warn!(price, mid_price = self.mid_price, "Ignoring price jump");
+                        }
+                        self.check_health().await;
                         self.refresh_orders().await;
                     }
-                    _ => {
-                        println!("[{}] Unhandled public message: {:?}", self.pair, pub_msg);
-                    }
+                    PublicData::Book(data) => self.on_book_data(&data).await,
                 },
-                WSPayload::OpenOrders(orders) => self.handle_order_update(orders).await,
-                WSPayload::Heartbeat(_heartbeat) => {}
-                _ => {
-                    println!("[{}] Unhandled message: {:?}", self.pair, data);
+                WSPayload::BookUpdate(update) => {
+                    self.on_book_data(&update.1).await;
+                    self.on_book_data(&update.2).await;
                 }
+                WSPayload::OpenOrders(orders) => self.handle_order_update(orders).await,
+                WSPayload::RequestStatus(status) => self.on_request_status(status),
+                WSPayload::SystemStatus(status) => self.on_system_status(status).await,
+                WSPayload::Heartbeat(_heartbeat) => self.check_health().await,
+                _ => debug!(message = ?data, "Unhandled message"),
             },
-            Err(e) => println!("[{}] Error: {}", self.pair, message),
+            Err(e) => {
+                METRICS.parse_errors.with_label_values(&[&self.pair]).inc();
+                warn!(error = %e, message, "Failed to parse message")
+            }
         }
     }
 
@@ -119,7 +268,6 @@ impl Market {
                 match order_data.status.as_str() {
                     "pending" | "open" => {
                         if order_data.descr.is_none() {
-                            // println!("[{}] Order descr is None", self.pair);
                             continue;
                         }
                         if order_data.descr.as_ref().unwrap().pair != self.pair {
@@ -128,61 +276,80 @@ impl Market {
                         match order_data.descr.as_ref().unwrap()._type.as_str() {
                             "buy" => {
                                 if !self.bid_orders.contains_key(&order_id) {
-                                    println!(
-                                        "[{}] Bid {}: {}",
-                                        self.pair, order_data.status, order_id
-                                    );
+                                    info!(%order_id, status = %order_data.status, "Bid opened");
+                                    self.journal_opened(&order_id, &order_data).await;
                                     self.bid_orders.insert(order_id.clone(), order_data);
                                 }
                             }
                             "sell" => {
                                 if !self.ask_orders.contains_key(&order_id) {
-                                    println!(
-                                        "[{}] Ask {}: {}",
-                                        self.pair, order_data.status, order_id
-                                    );
+                                    info!(%order_id, status = %order_data.status, "Ask opened");
+                                    self.journal_opened(&order_id, &order_data).await;
                                     self.ask_orders.insert(order_id.clone(), order_data);
                                 }
                             }
                             _ => {
-                                println!(
-                                    "[{}] Unhandled order type: {}",
-                                    self.pair,
-                                    order_data.descr.as_ref().unwrap()._type
+                                warn!(
+                                    %order_id,
+                                    order_type = %order_data.descr.as_ref().unwrap()._type,
+                                    "Unhandled order type"
                                 );
                             }
                         }
                     }
                     "closed" => {
-                        println!("[{}] Order filled: {}", self.pair, order_id);
+                        info!(%order_id, "Order closed");
+                        self.pending_cancels.remove(&order_id);
                         if let Some(order) = self.bid_orders.remove(&order_id) {
-                            self.on_order_filled(order).await;
+                            self.on_order_filled(order_id, order, order_data).await;
                         } else if let Some(order) = self.ask_orders.remove(&order_id) {
-                            self.on_order_filled(order).await;
+                            self.on_order_filled(order_id, order, order_data).await;
                         }
                     }
-                    "canceled" => {
-                        if let Some(_) = self.bid_orders.remove(&order_id) {
-                            println!("[{}] Bid cancelled: {}", self.pair, order_id)
-                        } else if let Some(_) = self.ask_orders.remove(&order_id) {
-                            println!("[{}] Ask cancelled: {}", self.pair, order_id)
+                    "canceled" | "expired" => {
+                        self.pending_cancels.remove(&order_id);
+                        let removed = if self.bid_orders.remove(&order_id).is_some() {
+                            info!(%order_id, "Bid cancelled");
+                            true
+                        } else if self.ask_orders.remove(&order_id).is_some() {
+                            info!(%order_id, "Ask cancelled");
+                            true
+                        } else {
+                            false
+                        };
+                        if removed {
+                            METRICS
+                                .orders_canceled
+                                .with_label_values(&[&self.pair])
+                                .inc();
+                            self.journal.lock().await.append(Event::OrderCanceled {
+                                pair: self.pair.clone(),
+                                order_id,
+                            });
                         }
                     }
                     _ => {
-                        println!(
-                            "[{}] Unhandled order status: {}",
-                            self.pair, order_data.status
-                        );
+                        warn!(%order_id, status = %order_data.status, "Unhandled order status");
                     }
                 }
             }
         }
+        let reserved = self.get_reserved_cash();
+        self.portfolio
+            .lock()
+            .await
+            .set_reserved_cash(self.pair.clone(), reserved);
     }
 
     async fn on_ticker_data(&mut self, data: TickerData) {
         let bid_price = data.b[0].as_str().unwrap().parse::<f64>().unwrap();
         let ask_price = data.a[0].as_str().unwrap().parse::<f64>().unwrap();
         self.record_spread(bid_price, ask_price);
+        self.touch = Some((bid_price, ask_price));
+        let now = self.clock.now_secs();
+        self.circuit_breaker
+            .on_ticker(now, (bid_price + ask_price) / 2.0);
+        self.check_health().await;
 
         // Initialize
         let decimals = count_decimals(&bid_price.to_string());
@@ -199,15 +366,141 @@ impl Market {
         self.refresh_orders().await;
     }
 
-    async fn on_order_filled(&mut self, order: OrderData) {
-        println!("[{}] Order filled: {:?}", self.pair, order);
+    /// Pauses quoting and cancels orders when the circuit breaker finds a problem with the
+    /// market data, and resumes once it clears. Also called on a timer, since a dead socket
+    /// sends nothing to trigger it.
+    pub async fn check_health(&mut self) {
+        let now = self.clock.now_secs();
+        let problem = self.circuit_breaker.check(now);
+        match (&self.unhealthy, &problem) {
+            (None, Some(reason)) => {
+                warn!(%reason, "Market data unhealthy, pausing quoting");
+                METRICS
+                    .circuit_breaker_trips
+                    .with_label_values(&[&self.pair])
+                    .inc();
+                self.cancel_all_orders().await;
+            }
+            (Some(_), None) => info!("Market data healthy again, resuming quoting"),
+            _ => {}
+        }
+        self.unhealthy = problem;
+    }
+
+    /// Tracks the exchange status. Orders aren't placed during maintenance or cancel_only, and
+    /// are forced post-only during post_only.
+    async fn on_system_status(&mut self, status: SystemStatus) {
+        if status.status == self.exchange_status {
+            return;
+        }
+        if status.status == "online" {
+            info!(previous = %self.exchange_status, "Exchange back online");
+        } else {
+            warn!(status = %status.status, "Exchange status changed");
+        }
+        self.exchange_status = status.status;
+        self.refresh_orders().await;
+    }
+
+    /// Returns true if the exchange currently accepts new orders.
+    fn can_place_orders(&self) -> bool {
+        !matches!(self.exchange_status.as_str(), "maintenance" | "cancel_only")
+    }
+
+    fn on_request_status(&mut self, status: RequestStatus) {
+        if status.status != "error" {
+            return;
+        }
+        if status.event == "addOrderStatus" {
+            METRICS
+                .orders_rejected
+                .with_label_values(&[&self.pair])
+                .inc();
+            if status.error_message.as_deref() == Some("EOrder:Post only order") {
+                // The quote would have crossed the spread, requote shortly on fresher prices
+                info!("Post-only order rejected, requoting");
+                let now = self.clock.now_secs();
+                self.last_order_time =
+                    (now + POST_ONLY_RETRY_DELAY).saturating_sub(self.order_cooldown);
+                return;
+            }
+        }
+        warn!(
+            event = %status.event,
+            error = %status.error_message.unwrap_or_default(),
+            "Request failed"
+        );
+    }
+
+    async fn on_book_data(&mut self, data: &BookData) {
+        if let Err(mismatch) = self.book.apply(data) {
+            warn!(
+                expected = mismatch.expected,
+                actual = mismatch.actual,
+                "Book checksum mismatch, resubscribing"
+            );
+            self.resubscribe_book().await;
+        }
+    }
+
+    /// Drops the local book and asks for a fresh snapshot.
+    async fn resubscribe_book(&mut self) {
+        for event in ["unsubscribe", "subscribe"] {
+            let message = json!(
+                {
+                    "event": event,
+                    "pair": [self.pair],
+                    "subscription": {
+                        "name": "book",
+                        "depth": BOOK_DEPTH
+                    }
+                }
+            )
+            .to_string();
+            send(&mut self.pub_sink, &message).await.unwrap();
+        }
+    }
+
+    async fn journal_opened(&self, order_id: &str, order: &OrderData) {
+        let descr = order.descr.as_ref().unwrap();
+        self.journal.lock().await.append(Event::OrderOpened {
+            pair: self.pair.clone(),
+            order_id: order_id.to_string(),
+            side: descr._type.clone(),
+            price: descr.price.parse::<f64>().unwrap_or_default(),
+            volume: parse_or_zero(&order.vol),
+        });
+    }
+
+    /// Updates the portfolio and journal with a filled order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - The order as it was first reported open.
+    /// * `update` - The update closing the order, which carries the fee charged.
+    async fn on_order_filled(&mut self, order_id: String, order: OrderData, update: OrderData) {
+        info!(%order_id, order = ?order, "Order filled");
 
         let descr = &order.descr.unwrap();
         let order_price = descr.price.parse::<f64>().unwrap();
         let order_vol = order.vol.unwrap().parse::<f64>().unwrap();
+        let fee = parse_or_zero(&update.fee);
         self.set_last_price(order_price);
-
         {
+            let now = self.clock.now_secs();
+            self.controls.lock().await.kill_switch.record_fill(now);
+        }
+        METRICS
+            .orders_filled
+            .with_label_values(&[&self.pair, &descr._type])
+            .inc();
+        METRICS
+            .fees_paid
+            .with_label_values(&[&self.pair])
+            .inc_by(fee.max(0.0)); // Counters can't go down
+        debug!(fee, rate = fee / (order_price * order_vol), "Fee charged");
+
+        let assets = {
             // Update portfolio balances
             let mut portfolio = self.portfolio.lock().await;
             if descr._type == "buy" {
@@ -215,117 +508,275 @@ impl Market {
             } else {
                 portfolio.update_pair(self.pair.clone(), -order_vol, order_price)
             };
+            portfolio.charge_fee(fee);
+            portfolio.get_assets()
+        };
+
+        {
+            let mut journal = self.journal.lock().await;
+            journal.append(Event::Fill {
+                pair: self.pair.clone(),
+                order_id,
+                side: descr._type.clone(),
+                price: order_price,
+                volume: order_vol,
+                fee,
+            });
+            journal.append(Event::Balances { assets });
         }
+        self.journal_state().await;
     }
 
+    /// Asks the strategy for its desired quotes and reconciles them with the live orders.
     async fn refresh_orders(&mut self) {
-        let mid_price = self.get_mid_price();
-        let target_delta = self.get_target_delta().await;
-        let now = time::UNIX_EPOCH.elapsed().unwrap().as_secs();
-        if target_delta == 0.0 || self.last_order_time + ORDER_CREATION_COOLDOWN > now {
+        let now = self.clock.now_secs();
+        if self.last_order_time + self.order_cooldown > now
+            || self.unhealthy.is_some()
+            || !self.can_place_orders()
+            || self.controls.lock().await.is_paused(&self.pair)
+        {
             return;
         }
 
-        println!("[{}] Target delta: {}", self.pair, target_delta);
-
-        if target_delta < -DELTA_THRESHOLD {
-            let ask_price = mid_price * (1.0 + MIN_SPREAD / 2.0);
-            if !Market::similar_order_exists("sell", ask_price, &self.ask_orders) {
-                self.cancel_orders().await;
-                let ask_size = self.get_ask_size(ask_price);
-                let sell_message = json!(
-                    {
-                        "event": "addOrder",
-                        "ordertype": "limit",
-                        "pair": self.pair,
-                        "price": self.round_price(ask_price),
-                        "token": self.token,
-                        "type": "sell",
-                        "volume": ask_size.to_string(),
-                    }
-                )
-                .to_string();
-                send(&mut self.priv_sink, &sell_message).await.unwrap();
+        let market = self.get_snapshot().await;
+        let portfolio = self.get_portfolio_snapshot().await;
+        let quotes = match self.strategy.quotes(&market, &portfolio) {
+            Some(quotes) => quotes,
+            None => return,
+        };
+        let quotes = self.ladder.expand(quotes, market.volatility);
+        let quotes = self.profitable_quotes(quotes, &market);
+
+        if portfolio.target_delta != 0.0 && !quotes.is_empty() {
+            debug!(target_delta = portfolio.target_delta, "Refreshing orders");
+        }
+
+        // Cancel live orders that no longer match a quote
+        let stale: Vec<String> = self
+            .bid_orders
+            .iter()
+            .chain(self.ask_orders.iter())
+            .filter(|(order_id, order)| {
+                !self.pending_cancels.contains(*order_id)
+                    && !quotes
+                        .iter()
+                        .any(|quote| Market::order_matches(order, quote))
+            })
+            .map(|(order_id, _)| order_id.clone())
+            .collect();
+        self.cancel_orders(stale).await;
+
+        // Place quotes that aren't already resting
+        let mut risk_context = self.get_risk_context(&portfolio);
+        let mut placed = false;
+        for quote in quotes.iter() {
+            let orders = match quote.side {
+                Side::Buy => &self.bid_orders,
+                Side::Sell => &self.ask_orders,
+            };
+            if Market::similar_order_exists(quote, orders) {
+                continue;
             }
-        } else if target_delta > DELTA_THRESHOLD {
-            let bid_price = mid_price * (1.0 - MIN_SPREAD / 2.0);
-            if !Market::similar_order_exists("buy", bid_price, &self.bid_orders) {
-                self.cancel_orders().await;
-                let bid_size = self.get_bid_size(bid_price);
-                let buy_message = json!(
-                    {
-                        "event": "addOrder",
-                        "ordertype": "limit",
-                        "pair": self.pair,
-                        "price": self.round_price(bid_price),
-                        "token": self.token,
-                        "type": "buy",
-                        "volume": bid_size.to_string(),
-                    }
-                )
-                .to_string();
-                send(&mut self.priv_sink, &buy_message).await.unwrap();
+            // Check and reserve under one lock, so markets can't spend the same cash
+            let checked = {
+                let mut portfolio = self.portfolio.lock().await;
+                risk_context.other_reserved_cash = portfolio.get_reserved_cash_except(&self.pair);
+                let checked = self.risk.check(quote, &risk_context);
+                if let Ok(quote) = &checked {
+                    risk_context.reserve(quote);
+                    portfolio.set_reserved_cash(self.pair.clone(), risk_context.reserved_cash);
+                }
+                checked
+            };
+            match checked {
+                Ok(quote) => {
+                    self.add_order(&quote).await;
+                    placed = true;
+                }
+                Err(rejection) => {
+                    METRICS
+                        .risk_rejections
+                        .with_label_values(&[&self.pair, rejection.reason()])
+                        .inc();
+                    warn!(
+                        side = quote.side.as_str(),
+                        price = quote.price,
+                        volume = quote.size,
+                        %rejection,
+                        "Quote rejected by risk checks"
+                    );
+                }
             }
-        } else {
-            self.cancel_orders().await;
-            return;
         }
-        self.last_order_time = now;
+        if placed {
+            self.last_order_time = now;
+            self.journal_state().await;
+        }
     }
 
-    async fn cancel_orders(&mut self) {
-        let keys: Vec<&str> = self
+    /// Drops quotes whose expected edge doesn't pay for a round trip's fees. The edge is the
+    /// spread to the nearest quote on the other side, or twice the distance from mid for a lone
+    /// quote. A quote through the book pays the taker fee unless it is sent post-only.
+    fn profitable_quotes(&self, quotes: Vec<Quote>, market: &MarketSnapshot) -> Vec<Quote> {
+        let post_only = self.order_config.post_only || self.exchange_status == "post_only";
+        let best = |side: Side| {
+            let prices = quotes.iter().filter(|q| q.side == side).map(|q| q.price);
+            match side {
+                Side::Buy => prices.reduce(f64::max),
+                Side::Sell => prices.reduce(f64::min),
+            }
+        };
+        let (best_bid, best_ask) = (best(Side::Buy), best(Side::Sell));
+        let book = market.book.as_ref();
+        quotes
+            .iter()
+            .filter(|quote| {
+                let (spread, crosses) = match quote.side {
+                    Side::Buy => (
+                        best_ask.unwrap_or(2.0 * market.mid_price - quote.price) - quote.price,
+                        book.and_then(|b| b.best_ask())
+                            .is_some_and(|(ask, _)| quote.price >= ask),
+                    ),
+                    Side::Sell => (
+                        quote.price - best_bid.unwrap_or(2.0 * market.mid_price - quote.price),
+                        book.and_then(|b| b.best_bid())
+                            .is_some_and(|(bid, _)| quote.price <= bid),
+                    ),
+                };
+                let taker = crosses && !post_only;
+                let profitable = market.fees.is_profitable(spread / market.mid_price, taker);
+                if !profitable {
+                    debug!(
+                        side = quote.side.as_str(),
+                        price = quote.price,
+                        spread,
+                        taker,
+                        "Skipping quote that doesn't cover fees"
+                    );
+                }
+                profitable
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Refreshes orders immediately, ignoring the order cooldown.
+    pub async fn rebalance(&mut self) {
+        self.last_order_time = 0;
+        self.refresh_orders().await;
+    }
+
+    /// Cancels every live order for this pair.
+    pub async fn cancel_all_orders(&mut self) {
+        let order_ids: Vec<String> = self
             .bid_orders
             .keys()
             .chain(self.ask_orders.keys())
-            .map(|k| (*k).as_str())
+            .filter(|order_id| !self.pending_cancels.contains(*order_id))
+            .cloned()
             .collect();
-        if !keys.is_empty() {
+        self.cancel_orders(order_ids).await;
+    }
+
+    async fn add_order(&mut self, quote: &Quote) {
+        info!(
+            side = quote.side.as_str(),
+            price = quote.price,
+            volume = quote.size,
+            "Placing order"
+        );
+        METRICS
+            .orders_placed
+            .with_label_values(&[&self.pair, quote.side.as_str()])
+            .inc();
+        self.journal.lock().await.append(Event::OrderPlaced {
+            pair: self.pair.clone(),
+            side: quote.side.as_str().to_string(),
+            price: quote.price,
+            volume: quote.size,
+        });
+        let mut message = json!(
+            {
+                "event": "addOrder",
+                "ordertype": "limit",
+                "pair": self.pair,
+                "price": self.round_price(quote.price),
+                "token": self.token,
+                "type": quote.side.as_str(),
+                "volume": quote.size.to_string(),
+            }
+        );
+        if self.order_config.post_only || self.exchange_status == "post_only" {
+            message["oflags"] = json!("post");
+        }
+        if let Some(time_in_force) = self.order_config.time_in_force {
+            message["timeinforce"] = json!(time_in_force.as_str());
+        }
+        if let Some(expire_after) = self.order_config.expire_after {
+            message["expiretm"] = json!(format!("+{}", expire_after));
+        }
+        let message = message.to_string();
+        send(&mut self.priv_sink, &message).await.unwrap();
+    }
+
+    async fn cancel_orders(&mut self, order_ids: Vec<String>) {
+        if !order_ids.is_empty() {
+            info!(order_ids = ?order_ids, "Cancelling orders");
             let message = json!(
                 {
                     "event": "cancelOrder",
                     "token": self.token,
-                    "txid": keys
+                    "txid": order_ids
                 }
             )
             .to_string();
             send(&mut self.priv_sink, &message).await.unwrap();
+            self.pending_cancels.extend(order_ids);
         }
     }
 
     /// Records self.prices if it has been PRICE_RECORD_INTERVAL seconds since the last recording.
     async fn record_price(&mut self, price: f64) {
-        let now = time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let now = self.clock.now_secs();
         if now - self.prices_last_updated >= PRICE_RECORD_INTERVAL && price != 0.0 {
             {
                 let mut portfolio = self.portfolio.lock().await;
                 portfolio.set_pair_price(self.pair.clone(), price);
             }
 
+            self.volatility.record_price(self.clock.now(), price);
             self.prices.push_back(price);
             if self.prices.len() > BUFFER_SIZE {
                 self.prices.pop_front();
             }
             self.prices_last_updated = now;
 
-            println!("[{}] Recorded new price: {}", self.pair, price);
-            // let (reserve_price, optimal_spread) = self.get_ans_params().await;
-            // println!(
-            //     "[{}] DEBUG: reserve {}, spread: {}",
-            //     self.pair, reserve_price, optimal_spread
-            // );
-            // println!(
-            //     "[{}] Price scaling: {}, Optimal spread: {}",
-            //     self.pair,
-            //     reserve_price / self.get_mid_price() - 1.0,
-            //     optimal_spread
-            // );
+            debug!(price, "Recorded new price");
+        }
+    }
+
+    /// Tracks the in-progress OHLC bar and feeds it to the volatility estimator once a bar with
+    /// a later end time arrives.
+    fn record_bar(&mut self, data: &[serde_json::Value]) {
+        let field = |i: usize| data[i].as_str().unwrap().parse::<f64>().unwrap();
+        let bar = Bar {
+            time: field(1),
+            open: field(2),
+            high: field(3),
+            low: field(4),
+            close: field(5),
+        };
+        if let Some(current) = self.current_bar {
+            if bar.time > current.time {
+                self.volatility.record_bar(current);
+            }
         }
+        self.current_bar = Some(bar);
     }
 
     /// Records the spread if it has been PRICE_RECORD_INTERVAL seconds since the last recording.
     fn record_spread(&mut self, bid_price: f64, ask_price: f64) {
-        let now = time::UNIX_EPOCH.elapsed().unwrap().as_secs();
+        let now = self.clock.now_secs();
         if now - self.spreads_last_updated >= PRICE_RECORD_INTERVAL {
             let spread = 2.0 * (ask_price - bid_price) / (ask_price + bid_price);
             self.spreads.push_back(spread);
@@ -336,41 +787,6 @@ impl Market {
         }
     }
 
-    async fn get_ans_params(&mut self) -> (f64, f64) {
-        let volatility = self.get_volatility();
-        let reserve_price = self.get_reserve_price(volatility).await;
-        let optimal_spread = self.get_optimal_spread(volatility);
-
-        (reserve_price, optimal_spread)
-    }
-
-    async fn get_reserve_price(&self, o: f64) -> f64 {
-        let q = self.get_target_delta().await;
-        let s = self.get_mid_price();
-        let y = RISK_AVERSION;
-        // println!("[{}] Target delta: {}", self.pair, q);
-
-        s * (1.0 + (q / q.abs().sqrt()) * y * o.powf(2.0))
-    }
-
-    fn get_optimal_spread(&mut self, o: f64) -> f64 {
-        let y = RISK_AVERSION;
-        // let k = self.get_order_depth();
-
-        let mut spread = y * o.powf(2.0) + (1.0 + y / 50.0).ln() / 2000.0;
-        // let mut spread = y * o.powf(2.0) + (1.0 + y / k).ln() / 2000.0;
-
-        if spread < MIN_SPREAD {
-            spread = MIN_SPREAD;
-        }
-
-        // println!("[{}] Spread: {}, Volatility: {}", self.pair, spread, o);
-
-        // // Incorporate reserve price so doesn't cross mid price
-        // spread + 2.0 * (reserve_price / self.get_last_price() - 1.0).abs()
-        spread
-    }
-
     // Set last price to weighted avg of last price and new price
     fn set_last_price(&mut self, price: f64) {
         self.last_price = ((PRICE_UPDATE_DENOMINATOR - PRICE_UPDATE_NUMERATOR) * self.last_price
@@ -378,49 +794,112 @@ impl Market {
             / PRICE_UPDATE_DENOMINATOR;
     }
 
-    fn get_last_price(&self) -> f64 {
-        self.last_price
-    }
-
     // Return avg of last 10 prices
     fn get_mid_price(&self) -> f64 {
         self.mid_price
     }
 
-    fn get_bid_size(&mut self, bid_price: f64) -> f64 {
-        ORDER_SIZE_USD / bid_price
+    /// Returns true once a price is known.
+    pub fn is_ready(&self) -> bool {
+        self.mid_price != 0.0
     }
 
-    fn get_ask_size(&mut self, ask_price: f64) -> f64 {
-        ORDER_SIZE_USD / ask_price
+    /// Returns why the market data is unhealthy, if it is.
+    /// Returns the best bid and ask, once a ticker has arrived.
+    pub fn get_touch(&self) -> Option<(f64, f64)> {
+        self.touch
     }
 
-    /// Returns the target delta for the asset. In percentage.
-    async fn get_target_delta(&self) -> f64 {
-        let portfolio = self.portfolio.lock().await;
-        portfolio.get_pair_target_delta(self.pair.clone())
+    /// Returns the pair's fee rates for the account's volume tier.
+    pub async fn get_fees(&self) -> Fees {
+        self.portfolio.lock().await.get_fees(&self.pair)
     }
 
-    // Returns the standard deviation of the last 100 prices
-    fn get_volatility(&self) -> f64 {
-        let mut sum = 0.0;
-        let mut count = 0.0;
-        let mut variance = 0.0;
+    pub fn get_unhealthy(&self) -> Option<&str> {
+        self.unhealthy.as_deref()
+    }
 
-        for price in self.prices.iter() {
-            sum += price;
-            count += 1.0;
-        }
+    /// Returns the span tagging logs with this market's pair. Callers outside the market's
+    /// task instrument calls with it.
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
 
-        let mean = sum / count;
+    pub fn get_orders(&self) -> Vec<OrderSummary> {
+        self.bid_orders
+            .iter()
+            .chain(self.ask_orders.iter())
+            .filter_map(|(order_id, order)| {
+                let descr = order.descr.as_ref()?;
+                Some(OrderSummary {
+                    order_id: order_id.clone(),
+                    side: descr._type.clone(),
+                    price: descr.price.parse::<f64>().unwrap_or_default(),
+                    volume: parse_or_zero(&order.vol),
+                })
+            })
+            .collect()
+    }
 
-        for price in self.prices.iter() {
-            variance += (price - mean).powf(2.0);
+    async fn get_snapshot(&self) -> MarketSnapshot {
+        MarketSnapshot {
+            pair: self.pair.clone(),
+            mid_price: self.mid_price,
+            last_price: self.last_price,
+            prices: self.prices.iter().copied().collect(),
+            spreads: self.spreads.iter().copied().collect(),
+            vol_24hr: self.vol_24hr,
+            volatility: self.volatility.estimate(),
+            book: if self.book.is_valid() {
+                Some(self.book.clone())
+            } else {
+                None
+            },
+            fees: self.get_fees().await,
         }
+    }
 
-        variance /= count;
+    async fn get_portfolio_snapshot(&self) -> PortfolioSnapshot {
+        let portfolio = self.portfolio.lock().await;
+        let (amount, price) = portfolio.get_pair(self.pair.clone());
+        PortfolioSnapshot {
+            amount,
+            price,
+            total_value: portfolio.get_total_value(),
+            cash: portfolio.get_pair("ZUSD".to_string()).0,
+            target_delta: portfolio.get_pair_target_delta(self.pair.clone()),
+        }
+    }
 
-        variance.sqrt() / self.get_mid_price() + BASE_VOLATILITY // Normalize by the last price
+    /// Returns the USD committed to live bids that aren't being cancelled.
+    fn get_reserved_cash(&self) -> f64 {
+        self.bid_orders
+            .iter()
+            .filter(|(order_id, _)| !self.pending_cancels.contains(*order_id))
+            .map(|(_, order)| order_price(order) * parse_or_zero(&order.vol))
+            .sum()
+    }
+
+    /// Returns the context for risk checks, counting live orders that aren't being cancelled.
+    fn get_risk_context(&self, portfolio: &PortfolioSnapshot) -> RiskContext {
+        let live =
+            |(order_id, _): &(&String, &OrderData)| !self.pending_cancels.contains(*order_id);
+        let mut context = RiskContext {
+            mid_price: self.mid_price,
+            amount: portfolio.amount,
+            cash: portfolio.cash,
+            total_value: portfolio.total_value,
+            open_orders: self.bid_orders.iter().filter(live).count(),
+            reserved_cash: self.get_reserved_cash(),
+            reserved_amount: 0.0,
+            other_reserved_cash: 0.0, // Read when checking, under the portfolio lock
+            order_min: self.order_min,
+        };
+        for (_, order) in self.ask_orders.iter().filter(live) {
+            context.open_orders += 1;
+            context.reserved_amount += parse_or_zero(&order.vol);
+        }
+        context
     }
 
     fn round_price(&self, price: f64) -> String {
@@ -428,23 +907,40 @@ impl Market {
         ((price * factor).round() / factor).to_string()
     }
 
-    fn similar_order_exists(_type: &str, price: f64, orders: &HashMap<String, OrderData>) -> bool {
-        for (_, order) in orders {
-            if order.descr.is_none() {
-                continue;
-            }
-            let order_price = order.descr.as_ref().unwrap().price.parse::<f64>().unwrap();
+    fn similar_order_exists(quote: &Quote, orders: &HashMap<String, OrderData>) -> bool {
+        orders
+            .values()
+            .any(|order| Market::order_matches(order, quote))
+    }
 
-            if (1.0 - order_price / price).abs() < UPDATE_PRICE_THRESHOLD {
-                return true;
+    /// Returns true if the order is on the quote's side and within UPDATE_PRICE_THRESHOLD of it.
+    fn order_matches(order: &OrderData, quote: &Quote) -> bool {
+        match order.descr.as_ref() {
+            Some(descr) if descr._type == quote.side.as_str() => {
+                let order_price = descr.price.parse::<f64>().unwrap();
+                (1.0 - order_price / quote.price).abs() < UPDATE_PRICE_THRESHOLD
             }
+            _ => false,
         }
-        false
     }
 }
 
+fn order_price(order: &OrderData) -> f64 {
+    order
+        .descr
+        .as_ref()
+        .and_then(|descr| descr.price.parse::<f64>().ok())
+        .unwrap_or_default()
+}
+
+fn parse_or_zero(value: &Option<String>) -> f64 {
+    value
+        .as_ref()
+        .and_then(|v| v.parse::<f64>().ok())
+        .unwrap_or_default()
+}
+
 fn count_decimals(s: &str) -> u8 {
-    // println!("Counting decimals for {}", s);
     if let Some(pos) = s.find('.') {
         s[pos + 1..].len() as u8
     } else {
@@ -0,0 +1,184 @@
+use crate::config::RecorderConfig;
+use chrono::{DateTime, Utc};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+pub const FLUSH_INTERVAL: u64 = 5; // seconds, at most this much is lost in a crash
+const REDACTED: &str = "<redacted>";
+
+pub type SharedRecorder = Arc<Mutex<Recorder>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+}
+
+/// A single recorded WebSocket message.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Frame {
+    pub time: f64, // unix seconds, when received or sent
+    pub connection: String,
+    pub pair: String,
+    pub channel: String, // public or private
+    pub direction: Direction,
+    pub message: String,
+}
+
+/// Writes frames to gzipped JSONL files in the configured directory, starting a new file each
+/// UTC day or once `max_file_size_mb` of uncompressed frames have been written. Frames are
+/// buffered until the next `flush`.
+pub struct Recorder {
+    config: RecorderConfig,
+    file: Option<GzEncoder<File>>,
+    day: String,
+    written: u64, // Uncompressed bytes in the current file
+    files: u32,   // Files started by this process
+}
+
+impl Recorder {
+    pub fn new(config: RecorderConfig) -> Self {
+        Recorder {
+            config,
+            file: None,
+            day: String::new(),
+            written: 0,
+            files: 0,
+        }
+    }
+
+    pub fn record(&mut self, frame: &Frame) {
+        let line = serde_json::to_string(frame).unwrap() + "\n";
+        let now: DateTime<Utc> = time::SystemTime::now().into();
+        let day = now.format("%Y%m%d").to_string();
+        if self.file.is_none()
+            || day != self.day
+            || self.written + line.len() as u64 > self.config.max_file_size_mb * 1_000_000
+        {
+            self.rotate(&now);
+            self.day = day;
+        }
+
+        if let Some(file) = self.file.as_mut() {
+            match file.write_all(line.as_bytes()) {
+                Ok(_) => self.written += line.len() as u64,
+                Err(e) => warn!(error = %e, "Failed to write recording"),
+            }
+        }
+    }
+
+    /// Writes buffered frames to the file so they survive a crash.
+    pub fn flush(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            if let Err(e) = file.flush() {
+                warn!(error = %e, "Failed to flush recording");
+            }
+        }
+    }
+
+    /// Completes the current file, e.g. on shutdown.
+    pub fn finish(&mut self) {
+        if let Some(file) = self.file.take() {
+            if let Err(e) = file.finish() {
+                warn!(error = %e, "Failed to finish recording");
+            }
+        }
+    }
+
+    fn rotate(&mut self, now: &DateTime<Utc>) {
+        self.finish();
+        self.written = 0;
+        self.files += 1;
+
+        let dir = PathBuf::from(&self.config.dir);
+        let path = dir.join(format!(
+            "ws-{}-{}.jsonl.gz",
+            now.format("%Y%m%d-%H%M%S"),
+            self.files
+        ));
+        match fs::create_dir_all(&dir).and_then(|_| File::create(&path)) {
+            Ok(file) => {
+                info!(path = %path.display(), "Recording WebSocket traffic");
+                self.file = Some(GzEncoder::new(file, Compression::default()));
+            }
+            Err(e) => warn!(path = %path.display(), error = %e, "Failed to create recording"),
+        }
+    }
+}
+
+/// Flushes the recorder every FLUSH_INTERVAL.
+pub async fn flush_periodically(recorder: SharedRecorder) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(FLUSH_INTERVAL));
+    loop {
+        interval.tick().await;
+        recorder.lock().await.flush();
+    }
+}
+
+/// Returns the message with any private WebSocket token replaced, as sent in subscribe
+/// requests and with each order.
+fn redact(message: &str) -> String {
+    let mut json: Value = match serde_json::from_str(message) {
+        Ok(json) => json,
+        Err(_) => return message.to_string(),
+    };
+    let mut redacted = false;
+    for pointer in ["/token", "/subscription/token"] {
+        if let Some(token) = json.pointer_mut(pointer) {
+            *token = Value::from(REDACTED);
+            redacted = true;
+        }
+    }
+    if redacted {
+        json.to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+/// Records the frames of a single connection.
+#[derive(Clone)]
+pub struct Tap {
+    recorder: SharedRecorder,
+    connection: String,
+    pair: String,
+    channel: &'static str,
+}
+
+impl Tap {
+    pub fn new(recorder: SharedRecorder, pair: &str, channel: &'static str) -> Self {
+        Tap {
+            recorder,
+            connection: uuid::Uuid::new_v4().to_string(),
+            pair: pair.to_string(),
+            channel,
+        }
+    }
+
+    /// Records a message, redacting the token from outbound ones.
+    pub async fn record(&self, direction: Direction, message: &str) {
+        let message = match direction {
+            Direction::Inbound => message.to_string(),
+            Direction::Outbound => redact(message),
+        };
+        let frame = Frame {
+            time: time::UNIX_EPOCH.elapsed().unwrap().as_secs_f64(),
+            connection: self.connection.clone(),
+            pair: self.pair.clone(),
+            channel: self.channel.to_string(),
+            direction,
+            message,
+        };
+        self.recorder.lock().await.record(&frame);
+    }
+}
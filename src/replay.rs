@@ -0,0 +1,167 @@
+use crate::account::Portfolio;
+use crate::clock::SimulatedClock;
+use crate::config::{KillSwitchConfig, PairConfig};
+use crate::control::Controls;
+use crate::journal::Journal;
+use crate::product::Market;
+use crate::recorder::{Direction, Frame};
+use crate::websocket::Sender;
+use flate2::read::MultiGzDecoder;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Mutex;
+use tracing::{info, warn, Instrument};
+
+/// A message the bot sent during the replay.
+#[derive(Serialize, Debug)]
+pub struct Captured {
+    pub time: f64,
+    pub channel: &'static str,
+    pub message: String,
+}
+
+/// Orders a market sent during a replay.
+#[derive(Serialize, Debug, Default)]
+pub struct Summary {
+    pub buys: usize,
+    pub sells: usize,
+    pub bought_usd: f64, // Notional of the buy orders
+    pub sold_usd: f64,
+    pub cancels: usize,
+}
+
+impl Summary {
+    pub fn from_captured(captured: &[Captured]) -> Self {
+        let mut summary = Summary::default();
+        for message in captured.iter() {
+            let request: serde_json::Value = match serde_json::from_str(&message.message) {
+                Ok(request) => request,
+                Err(_) => continue,
+            };
+            let field = |name: &str| {
+                request[name]
+                    .as_str()
+                    .and_then(|value| value.parse::<f64>().ok())
+                    .unwrap_or(0.0)
+            };
+            match (request["event"].as_str(), request["type"].as_str()) {
+                (Some("addOrder"), Some("buy")) => {
+                    summary.buys += 1;
+                    summary.bought_usd += field("price") * field("volume");
+                }
+                (Some("addOrder"), Some("sell")) => {
+                    summary.sells += 1;
+                    summary.sold_usd += field("price") * field("volume");
+                }
+                (Some("cancelOrder"), _) => {
+                    summary.cancels += request["txid"].as_array().map(Vec::len).unwrap_or(0);
+                }
+                _ => {}
+            }
+        }
+        summary
+    }
+}
+
+/// Reads frames from recordings, ordered by time. A truncated final line, e.g. from a crash
+/// while recording, ends the file.
+pub fn read_frames(paths: &[String]) -> std::io::Result<Vec<Frame>> {
+    let mut frames = Vec::new();
+    for path in paths.iter() {
+        let reader = BufReader::new(MultiGzDecoder::new(File::open(path)?));
+        for line in reader.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => {
+                    warn!(path, error = %e, "Recording ends early");
+                    break;
+                }
+            };
+            match serde_json::from_str::<Frame>(&line) {
+                Ok(frame) => frames.push(frame),
+                Err(e) => warn!(path, error = %e, "Skipping recorded frame"),
+            }
+        }
+    }
+    frames.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(frames)
+}
+
+/// Feeds the pair's recorded inbound messages to a fresh Market on a simulated clock and
+/// returns every message it would have sent.
+///
+/// # Arguments
+///
+/// * `assets` - The portfolio's (amount, price) per asset when the recording starts.
+/// * `speed` - Multiple of the recorded speed to replay at, or None to replay without waiting.
+pub async fn run(
+    config: PairConfig,
+    frames: &[Frame],
+    assets: HashMap<String, (f64, f64)>,
+    kill_switch: KillSwitchConfig,
+    speed: Option<f64>,
+) -> Vec<Captured> {
+    let pair = config.pair.clone();
+    let frames: Vec<&Frame> = frames
+        .iter()
+        .filter(|frame| frame.pair == pair && frame.direction == Direction::Inbound)
+        .collect();
+    let start = match frames.first() {
+        Some(frame) => frame.time,
+        None => return vec![],
+    };
+
+    let clock = Arc::new(SimulatedClock::new(start));
+    let portfolio = Portfolio::from_assets(assets, clock.clone());
+    let (pub_sink, mut pub_captured) = Sender::capture();
+    let (priv_sink, mut priv_captured) = Sender::capture();
+    let mut market = Market::new(
+        config,
+        Arc::new(Mutex::new(portfolio)),
+        Arc::new(Mutex::new(Journal::memory(clock.clone()))),
+        Arc::new(Mutex::new(Controls::new(kill_switch))),
+        pub_sink,
+        priv_sink,
+        "replay".to_string(),
+        clock.clone(),
+    );
+
+    info!(%pair, frames = frames.len(), "Replaying");
+    let span = market.span();
+    let mut captured = Vec::new();
+    let mut last_time = start;
+    for frame in frames {
+        if let Some(speed) = speed {
+            let wait = (frame.time - last_time).max(0.0) / speed;
+            tokio::time::sleep(tokio::time::Duration::from_secs_f64(wait)).await;
+        }
+        last_time = frame.time;
+        clock.set(frame.time);
+        market
+            .on_message(frame.message.clone())
+            .instrument(span.clone())
+            .await;
+        drain(&mut pub_captured, frame.time, "public", &mut captured);
+        drain(&mut priv_captured, frame.time, "private", &mut captured);
+    }
+    captured
+}
+
+fn drain(
+    receiver: &mut UnboundedReceiver<String>,
+    time: f64,
+    channel: &'static str,
+    captured: &mut Vec<Captured>,
+) {
+    while let Ok(message) = receiver.try_recv() {
+        captured.push(Captured {
+            time,
+            channel,
+            message,
+        });
+    }
+}
@@ -0,0 +1,315 @@
+use crate::journal::{Entry, Event};
+use chrono::{DateTime, SecondsFormat};
+use serde::Serialize;
+use std::collections::HashMap;
+
+pub const REBALANCE_PERIOD: u64 = 86_400; // seconds, for the periodic rebalance benchmark
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 86_400.0;
+// Snapshots are also written after fills, so risk is measured on values sampled this often
+const SAMPLE_INTERVAL: u64 = 300; // seconds
+
+/// Return and risk of a value series. Rates are annualized, the risk free rate is taken as 0.
+/// Volatility, Sharpe and Sortino use returns over SAMPLE_INTERVAL.
+#[derive(Serialize, Debug, Default, Clone, Copy)]
+pub struct Performance {
+    pub total_return: f64,
+    pub annualized_return: f64,
+    pub volatility: f64,
+    pub sharpe: f64,
+    pub sortino: f64,
+    pub max_drawdown: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub start: u64,
+    pub end: u64,
+    pub snapshots: usize,
+    pub start_value: f64,
+    pub end_value: f64,
+    pub portfolio: Performance, // Time-weighted, excluding deposits and withdrawals
+    pub hodl: Performance,      // Holding the initial portfolio
+    pub rebalance: Performance, // Rebalancing to target weights every REBALANCE_PERIOD, no fees
+    pub fees: f64,
+    pub fee_drag: f64, // Annualized fees over the average value
+    pub turnover: f64, // Annualized traded notional over the average value
+    /// Root mean square distance of each asset's weight from its target.
+    pub weight_tracking_error: f64,
+    pub tracking_error_vs_hodl: f64,
+    pub tracking_error_vs_rebalance: f64,
+}
+
+type Assets = HashMap<String, (f64, f64)>; // (amount, price)
+
+/// Builds a report from the journal's balance snapshots and fills, or None unless snapshots
+/// where every asset is priced span at least SAMPLE_INTERVAL. Changes in balances not
+/// explained by fills are treated as external flows and excluded from returns.
+pub fn generate(entries: &[Entry], rebalance_period: u64) -> Option<Report> {
+    let mut snapshots: Vec<(u64, Assets)> = Vec::new();
+    let mut expected: Option<Assets> = None; // The last snapshot plus later fills
+    let mut flows = vec![];
+    let mut fees = 0.0;
+    let mut traded = 0.0;
+
+    for entry in entries.iter() {
+        match &entry.event {
+            Event::Balances { assets } => {
+                if assets.values().any(|(_, price)| *price == 0.0) {
+                    continue;
+                }
+                let flow = match expected.as_ref() {
+                    Some(expected) => assets
+                        .iter()
+                        .map(|(asset, (amount, price))| {
+                            let before = expected.get(asset).map(|(a, _)| *a).unwrap_or(0.0);
+                            (amount - before) * price
+                        })
+                        .sum(),
+                    None => 0.0,
+                };
+                flows.push(flow);
+                snapshots.push((entry.time, assets.clone()));
+                expected = Some(assets.clone());
+            }
+            Event::Fill {
+                pair,
+                side,
+                price,
+                volume,
+                fee,
+                ..
+            } => {
+                if expected.is_none() {
+                    continue;
+                }
+                fees += fee;
+                traded += price * volume;
+                let expected = expected.as_mut().unwrap();
+                let asset = pair.strip_suffix("/USD").unwrap_or(pair);
+                let signed = if side == "buy" { *volume } else { -volume };
+                expected.entry(asset.to_string()).or_insert((0.0, *price)).0 += signed;
+                expected.entry("ZUSD".to_string()).or_insert((0.0, 1.0)).0 -= signed * price + fee;
+            }
+            _ => {}
+        }
+    }
+    let (start, end) = match (snapshots.first(), snapshots.last()) {
+        (Some((start, _)), Some((end, _))) if end.saturating_sub(*start) >= SAMPLE_INTERVAL => {
+            (*start, *end)
+        }
+        _ => return None,
+    };
+    let initial = snapshots[0].1.clone();
+    let years = (end - start) as f64 / SECONDS_PER_YEAR;
+    let periods_per_year = SECONDS_PER_YEAR / SAMPLE_INTERVAL as f64;
+
+    // Time-weighted index of the portfolio, compounding the returns net of flows
+    let values: Vec<f64> = snapshots.iter().map(|(_, assets)| value(assets)).collect();
+    let mut index = vec![1.0];
+    for i in 1..values.len() {
+        index.push(index[i - 1] * (values[i] - flows[i]) / values[i - 1]);
+    }
+
+    // Benchmarks, valued at each snapshot's prices
+    let hodl: Vec<f64> = snapshots
+        .iter()
+        .map(|(_, assets)| revalue(&initial, assets))
+        .collect();
+    let mut rebalance = Vec::new();
+    let mut holdings = target_holdings(&initial, value(&initial));
+    let mut last_rebalance = start;
+    for (time, assets) in snapshots.iter() {
+        let value = revalue(&holdings, assets);
+        rebalance.push(value);
+        if time - last_rebalance >= rebalance_period {
+            holdings = target_holdings(assets, value);
+            last_rebalance = *time;
+        }
+    }
+    let times: Vec<u64> = snapshots.iter().map(|(time, _)| *time).collect();
+    let returns = series_returns(&resample(&times, &index));
+    let hodl_returns = series_returns(&resample(&times, &hodl));
+    let rebalance_returns = series_returns(&resample(&times, &rebalance));
+
+    let average_value = values.iter().sum::<f64>() / values.len() as f64;
+    let weight_errors: Vec<f64> = snapshots
+        .iter()
+        .flat_map(|(_, assets)| {
+            let total = value(assets);
+            let target = 1.0 / assets.len() as f64;
+            assets
+                .values()
+                .map(move |(amount, price)| (amount * price / total - target).powi(2))
+        })
+        .collect();
+
+    Some(Report {
+        start,
+        end,
+        snapshots: snapshots.len(),
+        start_value: values[0],
+        end_value: *values.last().unwrap(),
+        portfolio: performance(&index, &returns, years, periods_per_year),
+        hodl: performance(&hodl, &hodl_returns, years, periods_per_year),
+        rebalance: performance(&rebalance, &rebalance_returns, years, periods_per_year),
+        fees,
+        fee_drag: fees / average_value / years,
+        turnover: traded / average_value / years,
+        weight_tracking_error: (weight_errors.iter().sum::<f64>() / weight_errors.len() as f64)
+            .sqrt(),
+        tracking_error_vs_hodl: tracking_error(&returns, &hodl_returns, periods_per_year),
+        tracking_error_vs_rebalance: tracking_error(&returns, &rebalance_returns, periods_per_year),
+    })
+}
+
+impl Report {
+    /// Prints the report as a table comparing the portfolio to the benchmarks.
+    pub fn print_table(&self) {
+        println!(
+            "{} to {}, {} snapshots, value {:.2} -> {:.2}",
+            format_time(self.start),
+            format_time(self.end),
+            self.snapshots,
+            self.start_value,
+            self.end_value
+        );
+        println!();
+        println!(
+            "{:<20}{:>12}{:>12}{:>12}",
+            "", "Portfolio", "HODL", "Rebalance"
+        );
+        let columns = [&self.portfolio, &self.hodl, &self.rebalance];
+        let row = |name: &str, get: fn(&Performance) -> f64, percent: bool| {
+            let cells: Vec<String> = columns
+                .iter()
+                .map(|p| {
+                    if percent {
+                        format!("{:.2}%", get(p) * 100.0)
+                    } else {
+                        format!("{:.2}", get(p))
+                    }
+                })
+                .collect();
+            println!(
+                "{:<20}{:>12}{:>12}{:>12}",
+                name, cells[0], cells[1], cells[2]
+            );
+        };
+        row("Total return", |p| p.total_return, true);
+        row("Annualized return", |p| p.annualized_return, true);
+        row("Volatility", |p| p.volatility, true);
+        row("Sharpe", |p| p.sharpe, false);
+        row("Sortino", |p| p.sortino, false);
+        row("Max drawdown", |p| p.max_drawdown, true);
+        println!(
+            "{:<20}{:>12}{:>12.2}%{:>11.2}%",
+            "Tracking error",
+            "",
+            self.tracking_error_vs_hodl * 100.0,
+            self.tracking_error_vs_rebalance * 100.0
+        );
+        println!();
+        println!("{:<20}{:>12.2}", "Fees", self.fees);
+        println!("{:<20}{:>11.2}%", "Fee drag", self.fee_drag * 100.0);
+        println!("{:<20}{:>11.2}x", "Turnover", self.turnover);
+        println!(
+            "{:<20}{:>11.2}%",
+            "Weight error",
+            self.weight_tracking_error * 100.0
+        );
+    }
+}
+
+fn value(assets: &Assets) -> f64 {
+    assets.values().map(|(amount, price)| amount * price).sum()
+}
+
+/// Values the holdings at the snapshot's prices, keeping their own price for missing assets.
+fn revalue(holdings: &Assets, snapshot: &Assets) -> f64 {
+    holdings
+        .iter()
+        .map(|(asset, (amount, price))| {
+            amount * snapshot.get(asset).map(|(_, p)| *p).unwrap_or(*price)
+        })
+        .sum()
+}
+
+/// Returns equal weight holdings of `value` across the snapshot's assets.
+fn target_holdings(snapshot: &Assets, value: f64) -> Assets {
+    let target = value / snapshot.len() as f64;
+    snapshot
+        .iter()
+        .map(|(asset, (_, price))| (asset.clone(), (target / price, *price)))
+        .collect()
+}
+
+fn series_returns(values: &[f64]) -> Vec<f64> {
+    values.windows(2).map(|w| w[1] / w[0] - 1.0).collect()
+}
+
+/// Returns the latest value at each SAMPLE_INTERVAL from the first time.
+fn resample(times: &[u64], values: &[f64]) -> Vec<f64> {
+    let mut sampled = Vec::new();
+    let mut i = 0;
+    let mut time = times[0];
+    while time <= *times.last().unwrap() {
+        while i + 1 < times.len() && times[i + 1] <= time {
+            i += 1;
+        }
+        sampled.push(values[i]);
+        time += SAMPLE_INTERVAL;
+    }
+    sampled
+}
+
+/// Returns the performance of a value series, with risk measured on its sampled returns.
+fn performance(values: &[f64], returns: &[f64], years: f64, periods_per_year: f64) -> Performance {
+    let count = returns.len() as f64;
+    let mean = returns.iter().sum::<f64>() / count;
+    let stdev = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count).sqrt();
+    let downside = (returns.iter().map(|r| r.min(0.0).powi(2)).sum::<f64>() / count).sqrt();
+
+    let mut peak = values[0];
+    let mut max_drawdown = 0.0_f64;
+    for value in values.iter() {
+        peak = f64::max(peak, *value);
+        max_drawdown = max_drawdown.max(1.0 - value / peak);
+    }
+    let growth = values[values.len() - 1] / values[0];
+
+    let ratio = |deviation: f64| {
+        if deviation == 0.0 {
+            0.0
+        } else {
+            mean / deviation * periods_per_year.sqrt()
+        }
+    };
+    Performance {
+        total_return: growth - 1.0,
+        annualized_return: growth.powf(1.0 / years) - 1.0,
+        volatility: stdev * periods_per_year.sqrt(),
+        sharpe: ratio(stdev),
+        sortino: ratio(downside),
+        max_drawdown,
+    }
+}
+
+/// Returns the annualized standard deviation of the difference in returns.
+fn tracking_error(returns: &[f64], benchmark: &[f64], periods_per_year: f64) -> f64 {
+    let differences: Vec<f64> = returns
+        .iter()
+        .zip(benchmark.iter())
+        .map(|(r, b)| r - b)
+        .collect();
+    let count = differences.len() as f64;
+    let mean = differences.iter().sum::<f64>() / count;
+    let variance = differences.iter().map(|d| (d - mean).powi(2)).sum::<f64>() / count;
+    (variance * periods_per_year).sqrt()
+}
+
+fn format_time(time: u64) -> String {
+    DateTime::from_timestamp(time as i64, 0)
+        .unwrap()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
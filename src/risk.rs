@@ -0,0 +1,154 @@
+use crate::config::RiskConfig;
+use crate::strategy::{Quote, Side};
+use std::fmt;
+
+/// State a quote is checked against. Reservations cover this pair's live orders plus any
+/// placed earlier in the same refresh, and the cash held for other pairs' bids.
+#[derive(Debug, Clone)]
+pub struct RiskContext {
+    pub mid_price: f64,
+    pub amount: f64,      // Base asset held
+    pub cash: f64,        // USD held
+    pub total_value: f64, // Portfolio value in USD
+    pub open_orders: usize,
+    pub reserved_cash: f64,       // USD committed to live bids
+    pub reserved_amount: f64,     // Base asset committed to live asks
+    pub other_reserved_cash: f64, // USD committed to live bids on other pairs
+    pub order_min: f64,           // Smallest volume the exchange accepts, 0 if unknown
+}
+
+impl RiskContext {
+    /// Accounts for an order about to be placed.
+    pub fn reserve(&mut self, quote: &Quote) {
+        self.open_orders += 1;
+        match quote.side {
+            Side::Buy => self.reserved_cash += quote.price * quote.size,
+            Side::Sell => self.reserved_amount += quote.size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    NoPrice,
+    OutsideCollar { deviation_bps: f64 },
+    TooManyOrders { open: usize },
+    InsufficientCash { available: f64 },
+    InsufficientAsset { available: f64 },
+    MaxPosition { weight: f64 },
+    BelowOrderMin { size: f64, order_min: f64 },
+}
+
+impl Rejection {
+    /// Short label for metrics.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rejection::NoPrice => "no_price",
+            Rejection::OutsideCollar { .. } => "price_collar",
+            Rejection::TooManyOrders { .. } => "max_open_orders",
+            Rejection::InsufficientCash { .. } => "cash",
+            Rejection::InsufficientAsset { .. } => "asset",
+            Rejection::MaxPosition { .. } => "max_position",
+            Rejection::BelowOrderMin { .. } => "order_min",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::NoPrice => write!(f, "no mid price"),
+            Rejection::OutsideCollar { deviation_bps } => {
+                write!(f, "price {:.1} bps from mid", deviation_bps)
+            }
+            Rejection::TooManyOrders { open } => write!(f, "{} orders already open", open),
+            Rejection::InsufficientCash { available } => {
+                write!(f, "only {} USD available", available)
+            }
+            Rejection::InsufficientAsset { available } => {
+                write!(f, "only {} available to sell", available)
+            }
+            Rejection::MaxPosition { weight } => write!(f, "position weight already {}", weight),
+            Rejection::BelowOrderMin { size, order_min } => {
+                write!(f, "size {} below the exchange minimum {}", size, order_min)
+            }
+        }
+    }
+}
+
+/// Pre-trade checks applied to every quote before it is sent.
+pub struct RiskLimits {
+    config: RiskConfig,
+}
+
+impl RiskLimits {
+    pub fn new(config: RiskConfig) -> Self {
+        RiskLimits { config }
+    }
+
+    /// Returns the quote, possibly with a smaller size, or the reason it was rejected. Quotes
+    /// clipped below the exchange's minimum order size are rejected.
+    pub fn check(&self, quote: &Quote, ctx: &RiskContext) -> Result<Quote, Rejection> {
+        if ctx.mid_price <= 0.0 {
+            return Err(Rejection::NoPrice);
+        }
+
+        let deviation_bps = (quote.price / ctx.mid_price - 1.0).abs() * 10_000.0;
+        if deviation_bps > self.config.price_collar_bps {
+            return Err(Rejection::OutsideCollar { deviation_bps });
+        }
+
+        if ctx.open_orders >= self.config.max_open_orders {
+            return Err(Rejection::TooManyOrders {
+                open: ctx.open_orders,
+            });
+        }
+
+        let mut size = quote.size.min(self.config.max_order_notional / quote.price);
+        match quote.side {
+            Side::Buy => {
+                let available = ctx.cash
+                    - ctx.reserved_cash
+                    - ctx.other_reserved_cash
+                    - self.config.min_cash_reserve;
+                if available <= 0.0 {
+                    return Err(Rejection::InsufficientCash { available });
+                }
+                size = size.min(available / quote.price);
+
+                if let Some(max_weight) = self.config.max_position_weight {
+                    if ctx.total_value > 0.0 {
+                        let value = ctx.amount * ctx.mid_price + ctx.reserved_cash;
+                        let headroom = max_weight * ctx.total_value - value;
+                        if headroom <= 0.0 {
+                            return Err(Rejection::MaxPosition {
+                                weight: value / ctx.total_value,
+                            });
+                        }
+                        size = size.min(headroom / quote.price);
+                    }
+                }
+            }
+            Side::Sell => {
+                let available = ctx.amount - ctx.reserved_amount;
+                if available <= 0.0 {
+                    return Err(Rejection::InsufficientAsset { available });
+                }
+                size = size.min(available);
+            }
+        }
+
+        if size < ctx.order_min {
+            return Err(Rejection::BelowOrderMin {
+                size,
+                order_min: ctx.order_min,
+            });
+        }
+
+        Ok(Quote {
+            side: quote.side,
+            price: quote.price,
+            size,
+        })
+    }
+}
@@ -0,0 +1,74 @@
+use super::{MarketSnapshot, PortfolioSnapshot, Quote, Side, Strategy};
+use crate::config::AvellanedaStoikovConfig;
+
+/// Avellaneda–Stoikov market making, using the distance to the target weight as inventory.
+/// Quotes both sides around a reserve price that skews towards the target.
+pub struct AvellanedaStoikov {
+    config: AvellanedaStoikovConfig,
+}
+
+impl AvellanedaStoikov {
+    pub fn new(config: AvellanedaStoikovConfig) -> Self {
+        AvellanedaStoikov { config }
+    }
+
+    fn get_reserve_price(&self, s: f64, q: f64, o: f64) -> f64 {
+        let y = self.config.risk_aversion;
+        s * (1.0 + (q / q.abs().sqrt()) * y * o.powf(2.0))
+    }
+
+    fn get_optimal_spread(&self, o: f64, k: f64, min_spread: f64) -> f64 {
+        let y = self.config.risk_aversion;
+
+        let spread = y * o.powf(2.0) + (1.0 + y / k).ln() / 2000.0;
+        spread.max(min_spread)
+    }
+}
+
+impl Strategy for AvellanedaStoikov {
+    fn name(&self) -> &'static str {
+        "avellaneda_stoikov"
+    }
+
+    fn quotes(
+        &mut self,
+        market: &MarketSnapshot,
+        portfolio: &PortfolioSnapshot,
+    ) -> Option<Vec<Quote>> {
+        let q = portfolio.target_delta;
+        let s = market.mid_price;
+        if q == 0.0 {
+            return None;
+        }
+
+        let o = market.volatility? + self.config.base_volatility;
+        let reserve_price = self.get_reserve_price(s, q, o);
+        let k = match &market.book {
+            Some(book) if self.config.book_density => {
+                book.order_density().unwrap_or(self.config.order_density)
+            }
+            _ => self.config.order_density,
+        };
+        let spread = self.get_optimal_spread(o, k, market.fees.min_spread());
+        let last_price = if market.last_price == 0.0 {
+            s
+        } else {
+            market.last_price
+        };
+
+        let bid_price = last_price.min(reserve_price) * (1.0 - spread / 2.0);
+        let ask_price = last_price.max(reserve_price) * (1.0 + spread / 2.0);
+        Some(vec![
+            Quote {
+                side: Side::Buy,
+                price: bid_price,
+                size: self.config.order_size_usd / bid_price,
+            },
+            Quote {
+                side: Side::Sell,
+                price: ask_price,
+                size: self.config.order_size_usd / ask_price,
+            },
+        ])
+    }
+}
@@ -0,0 +1,48 @@
+use super::{Quote, Side};
+use crate::config::{LadderConfig, Spacing};
+
+/// Spreads a strategy's quotes over several price levels per side.
+pub struct Ladder {
+    config: LadderConfig,
+}
+
+impl Ladder {
+    pub fn new(config: LadderConfig) -> Self {
+        Ladder { config }
+    }
+
+    /// Expands each quote into the configured number of levels. The quote itself is the first
+    /// level, the rest step away from mid with geometrically scaled sizes.
+    ///
+    /// # Arguments
+    ///
+    /// * `quotes` - The quotes returned by the strategy.
+    /// * `volatility` - The current normalized volatility, required for volatility spacing.
+    pub fn expand(&self, quotes: Vec<Quote>, volatility: Option<f64>) -> Vec<Quote> {
+        let step = match (&self.config.spacing, volatility) {
+            (Spacing::Bps(bps), _) => bps / 10_000.0,
+            (Spacing::Volatility(multiple), Some(volatility)) => multiple * volatility,
+            (Spacing::Volatility(_), None) => return quotes, // Only quote the first level
+        };
+        if self.config.levels <= 1 || step <= 0.0 {
+            return quotes;
+        }
+
+        let mut ladder = Vec::with_capacity(quotes.len() * self.config.levels);
+        for quote in quotes {
+            for level in 0..self.config.levels {
+                let offset = step * level as f64;
+                let price = match quote.side {
+                    Side::Buy => quote.price * (1.0 - offset),
+                    Side::Sell => quote.price * (1.0 + offset),
+                };
+                ladder.push(Quote {
+                    side: quote.side,
+                    price,
+                    size: quote.size * self.config.size_scale.powi(level as i32),
+                });
+            }
+        }
+        ladder
+    }
+}
@@ -0,0 +1,90 @@
+use crate::book::OrderBook;
+use crate::config::StrategyConfig;
+use crate::fees::Fees;
+
+mod avellaneda_stoikov;
+pub use avellaneda_stoikov::AvellanedaStoikov;
+mod ladder;
+pub use ladder::Ladder;
+mod threshold;
+pub use threshold::Threshold;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// Returns the side as used by the Kraken API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+}
+
+/// A desired resting order.
+#[derive(Clone, Debug)]
+pub struct Quote {
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Market data handed to a strategy on every refresh.
+#[derive(Clone, Debug)]
+pub struct MarketSnapshot {
+    pub pair: String,
+    pub mid_price: f64,
+    pub last_price: f64,
+    pub prices: Vec<f64>,
+    pub spreads: Vec<f64>,
+    pub vol_24hr: f64,
+    pub volatility: Option<f64>, // None while the estimator warms up
+    pub book: Option<OrderBook>, // None until a valid snapshot is received
+    pub fees: Fees,
+}
+
+/// The portfolio as seen from a single pair.
+#[derive(Clone, Debug)]
+pub struct PortfolioSnapshot {
+    pub amount: f64,
+    pub price: f64,
+    pub total_value: f64,
+    pub cash: f64,         // USD held
+    pub target_delta: f64, // In percentage
+}
+
+/// Quoting logic for a single market. The market diffs the returned quotes against its live
+/// orders, so a strategy only needs to describe where it wants to be.
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Returns the quotes that should be resting on the book, or None to leave live orders
+    /// untouched (e.g. not enough data yet).
+    fn quotes(
+        &mut self,
+        market: &MarketSnapshot,
+        portfolio: &PortfolioSnapshot,
+    ) -> Option<Vec<Quote>>;
+
+    /// Returns any internal state that should survive a restart.
+    fn get_state(&self) -> Option<serde_json::Value> {
+        None
+    }
+
+    /// Restores state previously returned by `get_state`.
+    fn restore_state(&mut self, _state: serde_json::Value) {}
+}
+
+/// Builds the strategy described by the config.
+pub fn from_config(config: &StrategyConfig) -> Box<dyn Strategy> {
+    match config {
+        StrategyConfig::Threshold(config) => Box::new(Threshold::new(config.clone())),
+        StrategyConfig::AvellanedaStoikov(config) => {
+            Box::new(AvellanedaStoikov::new(config.clone()))
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use super::{MarketSnapshot, PortfolioSnapshot, Quote, Side, Strategy};
+use crate::config::ThresholdConfig;
+
+/// Rebalances by placing a single order just off mid whenever the pair drifts more than
+/// `delta_threshold` percent away from its target weight.
+pub struct Threshold {
+    config: ThresholdConfig,
+}
+
+impl Threshold {
+    pub fn new(config: ThresholdConfig) -> Self {
+        Threshold { config }
+    }
+}
+
+impl Strategy for Threshold {
+    fn name(&self) -> &'static str {
+        "threshold"
+    }
+
+    fn quotes(
+        &mut self,
+        market: &MarketSnapshot,
+        portfolio: &PortfolioSnapshot,
+    ) -> Option<Vec<Quote>> {
+        let target_delta = portfolio.target_delta;
+        if target_delta == 0.0 || market.mid_price == 0.0 {
+            return None;
+        }
+
+        let min_spread = market.fees.min_spread();
+        if target_delta < -self.config.delta_threshold {
+            let price = market.mid_price * (1.0 + min_spread / 2.0);
+            Some(vec![Quote {
+                side: Side::Sell,
+                price,
+                size: self.config.order_size_usd / price,
+            }])
+        } else if target_delta > self.config.delta_threshold {
+            let price = market.mid_price * (1.0 - min_spread / 2.0);
+            Some(vec![Quote {
+                side: Side::Buy,
+                price,
+                size: self.config.order_size_usd / price,
+            }])
+        } else {
+            Some(vec![])
+        }
+    }
+}
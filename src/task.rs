@@ -1,24 +1,72 @@
 use crate::account::{Portfolio, Signer};
+use crate::book::BOOK_DEPTH;
+use crate::clock::SharedClock;
+use crate::config::{Endpoints, PairConfig};
+use crate::control::{Controls, Markets};
+use crate::history;
+use crate::journal::Journal;
+use crate::paper;
 use crate::product::Market;
-use crate::websocket::{connect_private, connect_public, listener, send};
+use crate::recorder::{SharedRecorder, Tap};
+use crate::websocket::{connect, listener, send, Sender};
 use serde_json::json;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::task::JoinHandle;
+use tracing::{info_span, Instrument};
+
+const HEALTH_CHECK_INTERVAL: u64 = 5; // seconds
+
+/// State and settings shared by every market task.
+#[derive(Clone)]
+pub struct Context {
+    pub portfolio: Arc<Mutex<Portfolio>>,
+    pub journal: Arc<Mutex<Journal>>,
+    pub controls: Arc<Mutex<Controls>>,
+    pub markets: Markets,
+    /// Trading signer, or None to paper trade against live market data.
+    pub signer: Option<Arc<Mutex<Signer>>>,
+    pub recorder: Option<SharedRecorder>,
+    pub endpoints: Endpoints,
+    pub cache_dir: String,
+    pub clock: SharedClock,
+}
 
 /// Helps spawn task by fetching ws token. Returns a JoinHandle.
-pub async fn spawn(
-    pair: String,
-    portfolio: Arc<Mutex<Portfolio>>,
-    signer: Arc<Mutex<Signer>>,
-) -> JoinHandle<()> {
-    let token = { signer.lock().await.get_ws_token().await };
-    tokio::spawn(start(pair, portfolio, token))
+pub async fn spawn(config: PairConfig, context: Context) -> JoinHandle<()> {
+    let token = match &context.signer {
+        Some(signer) => signer.lock().await.get_ws_token().await,
+        None => "paper".to_string(),
+    };
+    let span = info_span!("market", pair = %config.pair);
+    tokio::spawn(start(config, context, token).instrument(span))
 }
 
-pub async fn start(pair: String, portfolio: Arc<Mutex<Portfolio>>, token: String) {
-    let (mut pub_sink, pub_reader) = connect_public().await.unwrap();
-    let (mut priv_sink, priv_reader) = connect_private().await.unwrap();
+pub async fn start(config: PairConfig, context: Context, token: String) {
+    let pair = config.pair.clone();
+    let tap = |channel| {
+        context
+            .recorder
+            .as_ref()
+            .map(|recorder| Tap::new(recorder.clone(), &pair, channel))
+    };
+    let (mut pub_sink, pub_reader) = connect(&context.endpoints.public_ws, tap("public"))
+        .await
+        .unwrap();
+    let mut readers = vec![pub_reader];
+    let (mut priv_sink, paper_orders) = match context.signer {
+        Some(_) => {
+            let (priv_sink, priv_reader) = connect(&context.endpoints.private_ws, tap("private"))
+                .await
+                .unwrap();
+            readers.push(priv_reader);
+            (priv_sink, None)
+        }
+        None => {
+            let (priv_sink, captured) = Sender::capture();
+            (priv_sink, Some(captured))
+        }
+    };
 
     // Sub to ticker
     let message = json!(
@@ -45,6 +93,19 @@ pub async fn start(pair: String, portfolio: Arc<Mutex<Portfolio>>, token: String
     .to_string();
     send(&mut pub_sink, &message).await.unwrap();
 
+    // Sub to order book
+    let message = json!(
+    {
+        "event": "subscribe",
+        "pair": [pair],
+        "subscription": {
+            "name": "book",
+            "depth": BOOK_DEPTH
+        }
+    })
+    .to_string();
+    send(&mut pub_sink, &message).await.unwrap();
+
     // Sub to open orders
     let message = json!(
     {
@@ -86,6 +147,44 @@ pub async fn start(pair: String, portfolio: Arc<Mutex<Portfolio>>, token: String
     // println!("Sending: {}", message);
     // send(&mut priv_sink, &message).await.unwrap();
 
-    let market = Arc::new(Mutex::new(Market::new(pair, portfolio, priv_sink, token)));
-    listener(pub_reader, priv_reader, market.clone()).await;
+    let history = history::load(
+        &context.endpoints.rest,
+        &context.cache_dir,
+        &pair,
+        context.clock.as_ref(),
+    )
+    .await;
+    let mut market = Market::new(
+        config,
+        context.portfolio,
+        context.journal,
+        context.controls,
+        pub_sink,
+        priv_sink,
+        token,
+        context.clock,
+    );
+    market.warm_start(history).await;
+    market.restore().await;
+
+    let market = Arc::new(Mutex::new(market));
+    context.markets.lock().await.insert(pair, market.clone());
+    if let Some(captured) = paper_orders {
+        tokio::spawn(paper::run(captured, market.clone()).in_current_span());
+    }
+    tokio::select! {
+        _ = listener(readers, market.clone()) => {}
+        _ = watch_health(market) => {}
+    }
+}
+
+/// Checks the market data health every HEALTH_CHECK_INTERVAL, so staleness is noticed even
+/// when no messages arrive.
+async fn watch_health(market: Arc<Mutex<Market>>) {
+    let mut interval =
+        tokio::time::interval(tokio::time::Duration::from_secs(HEALTH_CHECK_INTERVAL));
+    loop {
+        interval.tick().await;
+        market.lock().await.check_health().await;
+    }
 }
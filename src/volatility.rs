@@ -0,0 +1,215 @@
+use crate::config::VolatilityConfig;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Horizon every estimate is scaled to, one OHLC bar.
+pub const HORIZON: f64 = 60.0; // seconds
+
+/// A completed OHLC bar.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Bar {
+    pub time: f64, // End of the bar, unix seconds
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+/// Volatility estimate for a single pair, fed with sampled prices and completed 1 minute OHLC
+/// bars. Estimates are the standard deviation of log returns over HORIZON, so estimators fed
+/// at different rates can be compared and blended. Returns between sampled prices are scaled
+/// by the time between them.
+pub enum Volatility {
+    /// Standard deviation of the last `window` returns between sampled prices.
+    Stdev {
+        window: usize,
+        last: Option<(f64, f64)>, // (time, price)
+        returns: VecDeque<f64>,   // Per square root second
+    },
+    /// Exponentially weighted stdev of log returns between sampled prices.
+    Ewma {
+        lambda: f64,
+        min_samples: usize,
+        last: Option<(f64, f64)>, // (time, price)
+        variance: f64,            // Per second
+        samples: usize,
+    },
+    /// Range based estimator over the last `window` bars. Bars span HORIZON so need no scaling.
+    Parkinson { window: usize, bars: VecDeque<Bar> },
+    /// Range and open/close based estimator over the last `window` bars.
+    GarmanKlass { window: usize, bars: VecDeque<Bar> },
+    /// Weighted average of a short and long horizon estimate.
+    Blend {
+        short: Box<Volatility>,
+        long: Box<Volatility>,
+        weight: f64, // Weight of the short estimate
+    },
+}
+
+impl Volatility {
+    pub fn new(config: &VolatilityConfig) -> Self {
+        match config {
+            VolatilityConfig::Stdev { window } => Volatility::Stdev {
+                window: *window,
+                last: None,
+                returns: VecDeque::with_capacity(*window),
+            },
+            VolatilityConfig::Ewma {
+                halflife,
+                min_samples,
+            } => Volatility::Ewma {
+                lambda: 0.5_f64.powf(1.0 / halflife),
+                min_samples: *min_samples,
+                last: None,
+                variance: 0.0,
+                samples: 0,
+            },
+            VolatilityConfig::Parkinson { window } => Volatility::Parkinson {
+                window: *window,
+                bars: VecDeque::with_capacity(*window),
+            },
+            VolatilityConfig::GarmanKlass { window } => Volatility::GarmanKlass {
+                window: *window,
+                bars: VecDeque::with_capacity(*window),
+            },
+            VolatilityConfig::Blend {
+                short,
+                long,
+                weight,
+            } => Volatility::Blend {
+                short: Box::new(Volatility::new(short)),
+                long: Box::new(Volatility::new(long)),
+                weight: *weight,
+            },
+        }
+    }
+
+    /// Records a price sampled at `time`, unix seconds.
+    pub fn record_price(&mut self, time: f64, price: f64) {
+        if price <= 0.0 {
+            return;
+        }
+        match self {
+            Volatility::Stdev {
+                window,
+                last,
+                returns,
+            } => {
+                if let Some(r) = normalized_return(last, time, price) {
+                    returns.push_back(r);
+                    if returns.len() > *window {
+                        returns.pop_front();
+                    }
+                }
+            }
+            Volatility::Ewma {
+                lambda,
+                last,
+                variance,
+                samples,
+                ..
+            } => {
+                if let Some(r) = normalized_return(last, time, price) {
+                    *variance = if *samples == 0 {
+                        r.powi(2)
+                    } else {
+                        *lambda * *variance + (1.0 - *lambda) * r.powi(2)
+                    };
+                    *samples += 1;
+                }
+            }
+            Volatility::Parkinson { .. } | Volatility::GarmanKlass { .. } => {}
+            Volatility::Blend { short, long, .. } => {
+                short.record_price(time, price);
+                long.record_price(time, price);
+            }
+        }
+    }
+
+    pub fn record_bar(&mut self, bar: Bar) {
+        if bar.low <= 0.0 || bar.open <= 0.0 {
+            return;
+        }
+        match self {
+            Volatility::Parkinson { window, bars } | Volatility::GarmanKlass { window, bars } => {
+                bars.push_back(bar);
+                if bars.len() > *window {
+                    bars.pop_front();
+                }
+            }
+            Volatility::Stdev { .. } | Volatility::Ewma { .. } => {}
+            Volatility::Blend { short, long, .. } => {
+                short.record_bar(bar);
+                long.record_bar(bar);
+            }
+        }
+    }
+
+    /// Returns the current estimate, or None while warming up.
+    pub fn estimate(&self) -> Option<f64> {
+        match self {
+            Volatility::Stdev { returns, .. } => {
+                if returns.len() < 2 {
+                    return None;
+                }
+                let count = returns.len() as f64;
+                let mean = returns.iter().sum::<f64>() / count;
+                let variance = returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / count;
+                Some((variance * HORIZON).sqrt())
+            }
+            Volatility::Ewma {
+                min_samples,
+                variance,
+                samples,
+                ..
+            } => {
+                if *samples < (*min_samples).max(1) {
+                    return None;
+                }
+                Some((variance * HORIZON).sqrt())
+            }
+            Volatility::Parkinson { window, bars } => {
+                if bars.len() < *window {
+                    return None;
+                }
+                let sum: f64 = bars.iter().map(|b| (b.high / b.low).ln().powi(2)).sum();
+                Some((sum / (4.0 * 2.0_f64.ln() * bars.len() as f64)).sqrt())
+            }
+            Volatility::GarmanKlass { window, bars } => {
+                if bars.len() < *window {
+                    return None;
+                }
+                let sum: f64 = bars
+                    .iter()
+                    .map(|b| {
+                        0.5 * (b.high / b.low).ln().powi(2)
+                            - (2.0 * 2.0_f64.ln() - 1.0) * (b.close / b.open).ln().powi(2)
+                    })
+                    .sum();
+                Some((sum / bars.len() as f64).max(0.0).sqrt())
+            }
+            Volatility::Blend {
+                short,
+                long,
+                weight,
+            } => match (short.estimate(), long.estimate()) {
+                (Some(short), Some(long)) => Some(weight * short + (1.0 - weight) * long),
+                (Some(estimate), None) | (None, Some(estimate)) => Some(estimate),
+                (None, None) => None,
+            },
+        }
+    }
+}
+
+/// Returns the log return since the last sample divided by the square root of the time
+/// between them, and makes this sample the last.
+fn normalized_return(last: &mut Option<(f64, f64)>, time: f64, price: f64) -> Option<f64> {
+    let previous = last.replace((time, price));
+    let (last_time, last_price) = previous?;
+    let elapsed = time - last_time;
+    if elapsed <= 0.0 {
+        *last = previous; // Keep the earlier sample so the next return spans real time
+        return None;
+    }
+    Some((price / last_price).ln() / elapsed.sqrt())
+}
@@ -1,60 +1,105 @@
 use futures_util::stream::SplitSink;
-use futures_util::{stream::SelectAll, stream::SplitStream, SinkExt, StreamExt};
+use futures_util::{stream, stream::SplitStream, SinkExt, StreamExt};
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use tokio_tungstenite::tungstenite::{Error, Message};
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 
 use crate::product::Market;
+use crate::recorder::{Direction, Tap};
+use tracing::{error, info, instrument, warn};
 
 type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
-pub async fn connect_public() -> Result<(SplitSink<Socket, Message>, SplitStream<Socket>), Error> {
-    connect("wss://ws.kraken.com").await
+enum Outlet {
+    Socket(SplitSink<Socket, Message>),
+    Capture(mpsc::UnboundedSender<String>),
 }
 
-pub async fn connect_private() -> Result<(SplitSink<Socket, Message>, SplitStream<Socket>), Error> {
-    connect("wss://ws-auth.kraken.com").await
+/// Sending half of a connection.
+pub struct Sender {
+    outlet: Outlet,
+    tap: Option<Tap>,
 }
 
-async fn connect(url: &str) -> Result<(SplitSink<Socket, Message>, SplitStream<Socket>), Error> {
+impl Sender {
+    /// Returns a sender that hands messages to the receiver instead of sending them.
+    pub fn capture() -> (Sender, mpsc::UnboundedReceiver<String>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let sender = Sender {
+            outlet: Outlet::Capture(tx),
+            tap: None,
+        };
+        (sender, rx)
+    }
+}
+
+/// Receiving half of a connection.
+pub struct Receiver {
+    stream: SplitStream<Socket>,
+    tap: Option<Tap>,
+}
+
+#[instrument(name = "connection", skip(tap))]
+pub async fn connect(url: &str, tap: Option<Tap>) -> Result<(Sender, Receiver), Error> {
     match connect_async(url).await {
         Ok((socket, _)) => {
-            println!("Connected to Kraken");
+            info!(url, "Connected to Kraken");
             let (sink, stream) = socket.split();
-            Ok((sink, stream))
+            Ok((
+                Sender {
+                    outlet: Outlet::Socket(sink),
+                    tap: tap.clone(),
+                },
+                Receiver { stream, tap },
+            ))
         }
         Err(e) => {
-            println!("Failed to connect to Kraken: {}", e);
+            error!(url, error = %e, "Failed to connect to Kraken");
             Err(e)
         }
     }
 }
 
-pub async fn send(sink: &mut SplitSink<Socket, Message>, message: &str) -> Result<(), Error> {
-    // Send the subscription message
-    sink.send(Message::Text(message.to_string())).await
+pub async fn send(sender: &mut Sender, message: &str) -> Result<(), Error> {
+    if let Some(tap) = sender.tap.as_ref() {
+        tap.record(Direction::Outbound, message).await;
+    }
+    match &mut sender.outlet {
+        Outlet::Socket(sink) => sink.send(Message::Text(message.to_string())).await,
+        Outlet::Capture(tx) => {
+            let _ = tx.send(message.to_string()); // Nothing to do if the receiver is gone
+            Ok(())
+        }
+    }
 }
 
-/// Listens to messages from both public and private streams and calls market.on_message.
-pub async fn listener(
-    reader1: SplitStream<Socket>,
-    reader2: SplitStream<Socket>,
-    market: Arc<Mutex<Market>>,
-) {
-    let mut streams = SelectAll::new();
-    streams.push(reader1);
-    streams.push(reader2);
-
-    let read_future = streams.for_each(|message| async {
-        match message {
-            Err(e) => {
-                println!("Error reading from stream: {}", e);
-                // ignore
-            }
-            Ok(message) => {
-                market.lock().await.on_message(message.to_string()).await;
+/// Listens to messages from every stream and calls market.on_message.
+pub async fn listener(readers: Vec<Receiver>, market: Arc<Mutex<Market>>) {
+    let (taps, streams): (Vec<_>, Vec<_>) = readers
+        .into_iter()
+        .enumerate()
+        .map(|(i, reader)| (reader.tap, reader.stream.map(move |message| (i, message))))
+        .unzip();
+    let streams = stream::select_all(streams);
+
+    let read_future = streams.for_each(|(reader, message)| {
+        let tap = &taps[reader];
+        let market = &market;
+        async move {
+            match message {
+                Err(e) => {
+                    warn!(error = %e, "Error reading from stream");
+                    // ignore
+                }
+                Ok(message) => {
+                    let message = message.to_string();
+                    if let Some(tap) = tap {
+                        tap.record(Direction::Inbound, &message).await;
+                    }
+                    market.lock().await.on_message(message).await;
+                }
             }
         }
     });
@@ -0,0 +1,147 @@
+use rebalancer::account::Portfolio;
+use rebalancer::api::{self, AppState};
+use rebalancer::clock::{SharedClock, SystemClock};
+use rebalancer::config::KillSwitchConfig;
+use rebalancer::control::Controls;
+use rebalancer::journal::Journal;
+use rebalancer::metrics::METRICS;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use zeroize::Zeroizing;
+
+const TOKEN: &str = "test-token";
+
+struct Api {
+    url: String,
+    controls: Arc<Mutex<Controls>>,
+    journal_path: PathBuf,
+}
+
+/// Serves the API on a free local port, journaling to a temporary file.
+async fn serve(token: Option<&str>) -> Api {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let journal_path = std::env::temp_dir().join(format!("journal-{}.jsonl", uuid::Uuid::new_v4()));
+    let journal = Journal::open(journal_path.to_str().unwrap(), clock.clone()).unwrap();
+    let assets = HashMap::from([
+        ("ZUSD".to_string(), (1000.0, 1.0)),
+        ("XETH".to_string(), (0.1, 2000.0)),
+    ]);
+    let controls = Arc::new(Mutex::new(Controls::new(KillSwitchConfig::default())));
+    let state = AppState {
+        portfolio: Arc::new(Mutex::new(Portfolio::from_assets(assets, clock.clone()))),
+        markets: Arc::new(Mutex::new(HashMap::new())),
+        controls: controls.clone(),
+        journal: Arc::new(Mutex::new(journal)),
+        pairs: vec!["ETH/USD".to_string()],
+        token: token.map(|token| Arc::new(Zeroizing::new(token.to_string()))),
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(api::serve(state, listener));
+    Api {
+        url,
+        controls,
+        journal_path,
+    }
+}
+
+#[tokio::test]
+async fn rejects_unauthenticated_control_requests() {
+    let Api { url, controls, .. } = serve(Some(TOKEN)).await;
+    let client = reqwest::Client::new();
+
+    let response = client.post(format!("{}/pause", url)).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let response = client
+        .post(format!("{}/pause", url))
+        .bearer_auth("wrong-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(!controls.lock().await.is_paused_globally());
+
+    let response = client
+        .post(format!("{}/pause", url))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    assert!(controls.lock().await.is_paused_globally());
+}
+
+#[tokio::test]
+async fn control_routes_are_disabled_without_token() {
+    let Api { url, controls, .. } = serve(None).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{}/pause", url))
+        .bearer_auth("")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(!controls.lock().await.is_paused_globally());
+}
+
+#[tokio::test]
+async fn status_routes_are_open() {
+    let Api { url, .. } = serve(Some(TOKEN)).await;
+    let response = reqwest::get(format!("{}/portfolio", url)).await.unwrap();
+    assert_eq!(response.status(), 200);
+}
+
+#[tokio::test]
+async fn exports_fees_as_counter() {
+    let Api { url, .. } = serve(None).await;
+    METRICS
+        .fees_paid
+        .with_label_values(&["ETH/USD"])
+        .inc_by(0.25);
+    let body = reqwest::get(format!("{}/metrics", url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(body.contains("# TYPE rebalancer_fees_paid_usd_total counter"));
+    assert!(body.contains("rebalancer_fees_paid_usd_total{pair=\"ETH/USD\"} 0.25"));
+}
+
+#[tokio::test]
+async fn kill_switch_reset_needs_token_and_is_journaled() {
+    let api = serve(Some(TOKEN)).await;
+    api.controls
+        .lock()
+        .await
+        .kill_switch
+        .trip("drawdown of 12.00%".to_string());
+    let client = reqwest::Client::new();
+    let url = format!("{}/kill-switch/reset", api.url);
+
+    let response = client.post(&url).send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    assert!(api.controls.lock().await.kill_switch.is_tripped());
+
+    let response = client
+        .post(&url)
+        .bearer_auth(TOKEN)
+        .header("X-Operator", "alice")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+    assert!(!api.controls.lock().await.kill_switch.is_tripped());
+    let journal = fs::read_to_string(&api.journal_path).unwrap();
+    assert!(journal.contains(r#""type":"kill_switch_reset","by":"alice from 127.0.0.1:"#));
+
+    let response = client.post(&url).bearer_auth(TOKEN).send().await.unwrap();
+    assert_eq!(response.status(), 409);
+    fs::remove_file(&api.journal_path).unwrap();
+}
@@ -0,0 +1,108 @@
+use rebalancer::auth::{self, Credentials, CredentialsError, Role};
+use rebalancer::keystore;
+use std::fs;
+
+// Kraken's published example for signing a private request.
+const SECRET: &str =
+    "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+const NONCE: &str = "1616492376594";
+const PATH: &str = "/0/private/AddOrder";
+const POST_DATA: &str =
+    "nonce=1616492376594&ordertype=limit&pair=XBTUSD&price=37500&type=buy&volume=1.25";
+const SIGNATURE: &str =
+    "4/dpxb3iT4tp/ZCVEwSnEsLxx0bqyhLpdfOpc6fn7OR8+UClSV5n9E6aSS8MPtnRfp32bAb0nmbRn6H8ndwLUQ==";
+
+#[test]
+fn signs_kraken_test_vector() {
+    let credentials = Credentials::new("key", SECRET, None).unwrap();
+    assert_eq!(credentials.sign(PATH, NONCE, POST_DATA), SIGNATURE);
+}
+
+#[test]
+fn signature_covers_path_and_data() {
+    let credentials = Credentials::new("key", SECRET, None).unwrap();
+    assert_ne!(
+        credentials.sign("/0/private/CancelOrder", NONCE, POST_DATA),
+        SIGNATURE
+    );
+    assert_ne!(
+        credentials.sign(PATH, NONCE, &POST_DATA.replace("37500", "37501")),
+        SIGNATURE
+    );
+    assert_ne!(auth::sign(&[0; 64], PATH, NONCE, POST_DATA), SIGNATURE);
+}
+
+#[test]
+fn rejects_invalid_credentials() {
+    assert!(matches!(
+        Credentials::new("", SECRET, None),
+        Err(CredentialsError::InvalidKey)
+    ));
+    assert!(matches!(
+        Credentials::new("my key", SECRET, None),
+        Err(CredentialsError::InvalidKey)
+    ));
+    assert!(matches!(
+        Credentials::new("key", "not base64!", None),
+        Err(CredentialsError::InvalidSecret)
+    ));
+    assert!(matches!(
+        Credentials::new("key", "c2hvcnQ=", None),
+        Err(CredentialsError::SecretLength(5))
+    ));
+}
+
+#[test]
+fn trims_surrounding_whitespace() {
+    let credentials = Credentials::new(" key\n", &format!("{}\n", SECRET), None).unwrap();
+    assert_eq!(credentials.key(), "key");
+    assert_eq!(credentials.sign(PATH, NONCE, POST_DATA), SIGNATURE);
+}
+
+#[test]
+fn debug_output_is_redacted() {
+    let credentials = Credentials::new("my-key", SECRET, Some("123456".to_string())).unwrap();
+    let debug = format!("{:?}", credentials);
+    assert!(!debug.contains("my-key"));
+    assert!(!debug.contains("123456"));
+    assert!(!debug.contains(&SECRET[..8]));
+}
+
+#[test]
+fn keystore_round_trip() {
+    let path = std::env::temp_dir().join(format!("keystore-{}.json", uuid::Uuid::new_v4()));
+    let path = path.to_str().unwrap();
+    keystore::create(path, "key", SECRET, "passphrase").unwrap();
+    let contents = fs::read_to_string(path).unwrap();
+    assert!(!contents.contains(SECRET));
+
+    let credentials = keystore::open(path, "passphrase", None).unwrap();
+    assert_eq!(credentials.key(), "key");
+    assert_eq!(credentials.sign(PATH, NONCE, POST_DATA), SIGNATURE);
+    assert!(matches!(
+        keystore::open(path, "wrong", None),
+        Err(CredentialsError::Keystore(_))
+    ));
+    // Never overwrites an existing keystore
+    assert!(keystore::create(path, "key", SECRET, "passphrase").is_err());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn loads_secret_from_file() {
+    let path = std::env::temp_dir().join(format!("secret-{}", uuid::Uuid::new_v4()));
+    fs::write(&path, format!("{}\n", SECRET)).unwrap();
+    // The only test touching the environment
+    std::env::set_var("KRAKEN_READ_KEY", "key");
+    std::env::set_var("KRAKEN_READ_SECRET_FILE", &path);
+
+    let credentials = Credentials::load(Role::ReadOnly).unwrap().unwrap();
+    assert_eq!(credentials.sign(PATH, NONCE, POST_DATA), SIGNATURE);
+
+    std::env::remove_var("KRAKEN_READ_KEY");
+    assert!(matches!(
+        Credentials::load(Role::ReadOnly),
+        Err(CredentialsError::Missing(_))
+    ));
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,127 @@
+use rebalancer::book::OrderBook;
+use rebalancer::messages::BookData;
+
+// Kraken's documented checksum example, ten levels of 0.000005 either side of 0.05.
+const ASKS: [&str; 10] = [
+    "0.05005", "0.05010", "0.05015", "0.05020", "0.05025", "0.05030", "0.05035", "0.05040",
+    "0.05045", "0.05050",
+];
+const BIDS: [&str; 10] = [
+    "0.05000", "0.04995", "0.04990", "0.04980", "0.04975", "0.04970", "0.04965", "0.04960",
+    "0.04955", "0.04950",
+];
+const CHECKSUM: &str = "974947235";
+
+fn level(price: &str, volume: &str) -> Vec<String> {
+    vec![
+        price.to_string(),
+        volume.to_string(),
+        "1534614248.123678".to_string(),
+    ]
+}
+
+fn snapshot() -> BookData {
+    BookData {
+        snapshot_asks: Some(ASKS.iter().map(|p| level(p, "0.00000500")).collect()),
+        snapshot_bids: Some(BIDS.iter().map(|p| level(p, "0.00000500")).collect()),
+        asks: None,
+        bids: None,
+        checksum: None,
+    }
+}
+
+fn update(asks: Vec<Vec<String>>, bids: Vec<Vec<String>>, checksum: Option<&str>) -> BookData {
+    BookData {
+        snapshot_asks: None,
+        snapshot_bids: None,
+        asks: Some(asks),
+        bids: Some(bids),
+        checksum: checksum.map(str::to_string),
+    }
+}
+
+#[test]
+fn matches_kraken_checksum_example() {
+    let mut book = OrderBook::new();
+    book.apply(&snapshot()).unwrap();
+    assert_eq!(book.checksum().to_string(), CHECKSUM);
+    assert_eq!(book.best_bid(), Some((0.05, 0.000005)));
+    assert_eq!(book.best_ask(), Some((0.05005, 0.000005)));
+
+    // An update that changes nothing carries the same checksum
+    book.apply(&update(vec![], vec![], Some(CHECKSUM))).unwrap();
+    assert!(book.is_valid());
+}
+
+#[test]
+fn truncates_to_subscribed_depth() {
+    let mut book = OrderBook::new();
+    book.apply(&snapshot()).unwrap();
+    let asks = vec![level("0.05004", "0.00000100")];
+    let bids = vec![level("0.05001", "0.00000100")];
+    book.apply(&update(asks, bids, None)).unwrap();
+
+    assert_eq!(book.best_ask(), Some((0.05004, 0.000001)));
+    assert_eq!(book.best_bid(), Some((0.05001, 0.000001)));
+    // The worst level on each side fell out of the book
+    let mut expected = OrderBook::new();
+    let mut data = snapshot();
+    let asks = data.snapshot_asks.as_mut().unwrap();
+    asks.pop();
+    asks.insert(0, level("0.05004", "0.00000100"));
+    let bids = data.snapshot_bids.as_mut().unwrap();
+    bids.pop();
+    bids.insert(0, level("0.05001", "0.00000100"));
+    expected.apply(&data).unwrap();
+    assert_eq!(book.checksum(), expected.checksum());
+}
+
+#[test]
+fn applies_republished_levels() {
+    let mut book = OrderBook::new();
+    book.apply(&snapshot()).unwrap();
+    book.apply(&update(vec![level("0.05004", "0.00000100")], vec![], None))
+        .unwrap();
+
+    // Removing the new level brings the truncated one back, flagged as republished
+    let mut republished = level("0.05050", "0.00000500");
+    republished.push("r".to_string());
+    let asks = vec![level("0.05004", "0.00000000"), republished];
+    book.apply(&update(asks, vec![], Some(CHECKSUM))).unwrap();
+    assert_eq!(book.checksum().to_string(), CHECKSUM);
+}
+
+#[test]
+fn invalidates_on_checksum_mismatch() {
+    let mut book = OrderBook::new();
+    book.apply(&snapshot()).unwrap();
+    let asks = vec![level("0.05005", "0.00000600")];
+    let mismatch = book
+        .apply(&update(asks, vec![], Some(CHECKSUM)))
+        .unwrap_err();
+    assert_eq!(mismatch.expected.to_string(), CHECKSUM);
+    assert!(!book.is_valid());
+    assert_eq!(book.best_ask(), None);
+
+    // Updates are ignored until the next snapshot
+    book.apply(&update(vec![level("0.05005", "0.00000500")], vec![], None))
+        .unwrap();
+    assert_eq!(book.best_ask(), None);
+    book.apply(&snapshot()).unwrap();
+    assert!(book.is_valid());
+}
+
+#[test]
+fn estimates_order_density_per_percent() {
+    // All volume 2 bps from a 2000 mid
+    let data = BookData {
+        snapshot_asks: Some(vec![level("2000.40", "1.0")]),
+        snapshot_bids: Some(vec![level("1999.60", "1.0")]),
+        asks: None,
+        bids: None,
+        checksum: None,
+    };
+    let mut book = OrderBook::new();
+    book.apply(&data).unwrap();
+    assert!((book.order_density().unwrap() - 50.0).abs() < 1e-6);
+}
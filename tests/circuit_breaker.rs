@@ -0,0 +1,109 @@
+use rebalancer::circuit_breaker::CircuitBreaker;
+use rebalancer::clock::{Clock, SimulatedClock};
+use rebalancer::config::CircuitBreakerConfig;
+
+const START: f64 = 1_700_000_000.0;
+
+fn config() -> CircuitBreakerConfig {
+    CircuitBreakerConfig {
+        max_staleness: 60,
+        jump_sigmas: 10.0,
+        min_jump_bps: 50.0,
+        max_divergence_bps: 100.0,
+    }
+}
+
+#[test]
+fn healthy_before_first_update() {
+    let clock = SimulatedClock::new(START);
+    let breaker = CircuitBreaker::new(config());
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
+
+#[test]
+fn trips_when_stale_and_recovers_on_data() {
+    let clock = SimulatedClock::new(START);
+    let mut breaker = CircuitBreaker::new(config());
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+    breaker.on_ohlc(clock.now_secs(), 2000.0);
+
+    clock.set(START + 60.0);
+    assert_eq!(breaker.check(clock.now_secs()), None);
+    clock.set(START + 61.0);
+    assert_eq!(
+        breaker.check(clock.now_secs()),
+        Some("no data for 61s".to_string())
+    );
+
+    // Either feed is enough to be fresh again
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
+
+#[test]
+fn trips_on_jump_until_confirmed() {
+    let clock = SimulatedClock::new(START);
+    let mut breaker = CircuitBreaker::new(config());
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+
+    // 0.3% is within the 50 bps floor, 5% is not
+    assert!(breaker.accept_price(2006.0, 2000.0, None));
+    assert!(!breaker.accept_price(2100.0, 2000.0, None));
+    assert_eq!(
+        breaker.check(clock.now_secs()),
+        Some("price jump".to_string())
+    );
+
+    // A move that persists is accepted as real
+    assert!(!breaker.accept_price(2100.0, 2000.0, None));
+    assert!(breaker.accept_price(2100.0, 2000.0, None));
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
+
+#[test]
+fn jump_threshold_scales_with_volatility() {
+    let mut breaker = CircuitBreaker::new(config());
+    // 10 sigmas of 1% allows moves of about 10%
+    assert!(breaker.accept_price(2100.0, 2000.0, Some(0.01)));
+    assert!(!breaker.accept_price(2300.0, 2000.0, Some(0.01)));
+}
+
+#[test]
+fn recovers_from_jump_on_normal_price() {
+    let clock = SimulatedClock::new(START);
+    let mut breaker = CircuitBreaker::new(config());
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+
+    assert!(!breaker.accept_price(1000.0, 2000.0, None));
+    assert!(breaker.check(clock.now_secs()).is_some());
+    assert!(breaker.accept_price(2001.0, 2000.0, None));
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
+
+#[test]
+fn trips_on_divergence_between_feeds() {
+    let clock = SimulatedClock::new(START);
+    let mut breaker = CircuitBreaker::new(config());
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+    breaker.on_ohlc(clock.now_secs(), 2030.0);
+    assert_eq!(
+        breaker.check(clock.now_secs()),
+        Some("ticker and OHLC diverge by 148 bps".to_string())
+    );
+
+    clock.set(START + 10.0);
+    breaker.on_ohlc(clock.now_secs(), 2010.0);
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
+
+#[test]
+fn ignores_divergence_from_stale_feed() {
+    let clock = SimulatedClock::new(START);
+    let mut breaker = CircuitBreaker::new(config());
+    breaker.on_ohlc(clock.now_secs(), 2030.0);
+
+    // OHLC only updates once a minute, an old close isn't compared
+    clock.set(START + 61.0);
+    breaker.on_ticker(clock.now_secs(), 2000.0);
+    assert_eq!(breaker.check(clock.now_secs()), None);
+}
@@ -0,0 +1,97 @@
+use clap::{CommandFactory, Parser};
+use rebalancer::cli::{Cli, Command, ConfigCommand};
+use rebalancer::config::Config;
+
+#[test]
+fn definition_is_consistent() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn runs_by_default() {
+    let cli = Cli::try_parse_from(["rebalancer"]).unwrap();
+    assert!(cli.command.is_none());
+    assert!(cli.global.pairs.is_empty());
+}
+
+#[test]
+fn parses_global_flags_after_subcommand() {
+    let cli = Cli::try_parse_from([
+        "rebalancer",
+        "rebalance",
+        "--dry-run",
+        "--pairs",
+        "ETH/USD,XBT/USD",
+        "--config",
+        "other.toml",
+    ])
+    .unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Rebalance { dry_run: true })
+    ));
+    assert_eq!(cli.global.pairs, ["ETH/USD", "XBT/USD"]);
+    assert_eq!(cli.global.config, "other.toml");
+
+    let cli = Cli::try_parse_from(["rebalancer", "config", "validate"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Some(Command::Config(ConfigCommand::Validate))
+    ));
+    assert!(Cli::try_parse_from(["rebalancer", "export-trades", "--method", "avg"]).is_err());
+}
+
+#[test]
+fn selects_pairs() {
+    let mut config: Config = toml::from_str(
+        r#"
+        [[pairs]]
+        pair = "ETH/USD"
+        order_cooldown = 60
+        "#,
+    )
+    .unwrap();
+    config.select_pairs(&["ETH/USD".to_string(), "SOL/USD".to_string()]);
+    assert_eq!(config.pairs.len(), 2);
+    assert_eq!(config.pairs[0].order_cooldown, 60);
+    assert_eq!(config.pairs[1].pair, "SOL/USD");
+    assert!(config.validate().is_empty());
+}
+
+#[test]
+fn reports_config_problems() {
+    let config: Config = toml::from_str(
+        r#"
+        [[pairs]]
+        pair = "ETH/XBT"
+        orders = { time_in_force = "GTD" }
+
+        [[pairs]]
+        pair = "ETH/XBT"
+        ladder = { levels = 0 }
+        "#,
+    )
+    .unwrap();
+    let problems = config.validate();
+    assert!(problems.iter().any(|p| p.contains("only /USD pairs")));
+    assert!(problems.iter().any(|p| p.contains("more than once")));
+    assert!(problems.iter().any(|p| p.contains("GTD")));
+    assert!(problems.iter().any(|p| p.contains("ladder levels")));
+    assert!(Config::default().validate().is_empty());
+    assert!(Config::load("config.toml").unwrap().validate().is_empty());
+}
+
+#[test]
+fn reaches_api_on_its_bind_address() {
+    let mut config = Config::default();
+    assert_eq!(config.api_url(), "http://127.0.0.1:8080");
+    config.api_bind = "0.0.0.0".to_string();
+    assert_eq!(config.api_url(), "http://127.0.0.1:8080");
+    config.api_bind = "::".to_string();
+    assert_eq!(config.api_url(), "http://[::1]:8080");
+    config.api_bind = "fdaa::3".to_string();
+    assert_eq!(config.api_url(), "http://[fdaa::3]:8080");
+    config.api_bind = "fly-local-6pn".to_string();
+    config.api_port = 9000;
+    assert_eq!(config.api_url(), "http://fly-local-6pn:9000");
+}
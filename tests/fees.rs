@@ -0,0 +1,23 @@
+use rebalancer::fees::Fees;
+
+const FEES: Fees = Fees {
+    maker: 0.0025,
+    taker: 0.004,
+};
+
+#[test]
+fn round_trip_pays_taker_fee_on_entry() {
+    assert!((FEES.round_trip(false) - 0.005).abs() < 1e-12);
+    assert!((FEES.round_trip(true) - 0.0065).abs() < 1e-12);
+}
+
+#[test]
+fn min_spread_covers_maker_round_trip() {
+    assert!(FEES.is_profitable(FEES.min_spread(), false));
+    assert!(Fees::default().is_profitable(Fees::default().min_spread(), false));
+
+    // Crossing the book as a taker needs a wider spread than the floor gives
+    let fees = Fees::default();
+    assert!(!fees.is_profitable(fees.min_spread(), true));
+    assert!(fees.is_profitable(0.005, true));
+}
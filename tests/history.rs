@@ -0,0 +1,30 @@
+mod support;
+
+use rebalancer::clock::SimulatedClock;
+use rebalancer::history;
+use support::mock_kraken::MockKraken;
+
+const UNREACHABLE: &str = "http://127.0.0.1:1";
+
+#[tokio::test]
+async fn falls_back_to_cache_until_it_is_stale() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0)], 2000.0).await;
+    let dir = std::env::temp_dir().join(format!("cache-{}", uuid::Uuid::new_v4()));
+    let dir = dir.to_str().unwrap().to_string();
+    let clock = SimulatedClock::default();
+
+    let fetched = history::load(&mock.endpoints.rest, &dir, "ETH/USD", &clock).await;
+    assert!(!fetched.bars.is_empty());
+    assert_eq!(fetched.order_min, 0.002);
+
+    // An hour after the last bar the cache is still used
+    clock.set(fetched.last_updated() + 3600.0);
+    let cached = history::load(UNREACHABLE, &dir, "ETH/USD", &clock).await;
+    assert_eq!(cached.bars.len(), fetched.bars.len());
+    assert_eq!(cached.last_updated(), fetched.last_updated());
+
+    clock.set(fetched.last_updated() + 3601.0);
+    let stale = history::load(UNREACHABLE, &dir, "ETH/USD", &clock).await;
+    assert!(stale.bars.is_empty());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,157 @@
+use rebalancer::account::Portfolio;
+use rebalancer::clock::{Clock, SharedClock, SimulatedClock};
+use rebalancer::config::KillSwitchConfig;
+use rebalancer::control::{self, Controls, Markets};
+use rebalancer::journal::{Event, Journal};
+use rebalancer::kill_switch::KillSwitch;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+const START: f64 = 1_699_963_200.0; // Noon UTC, well clear of a day boundary
+const DAY: u64 = 86_400;
+
+fn config() -> KillSwitchConfig {
+    KillSwitchConfig {
+        max_daily_loss_pct: 5.0,
+        max_drawdown_pct: 10.0,
+        max_fills_per_hour: 3,
+    }
+}
+
+#[test]
+fn trips_on_daily_loss() {
+    let clock = SimulatedClock::new(START);
+    let mut kill_switch = KillSwitch::new(config());
+
+    assert_eq!(kill_switch.check(clock.now_secs(), 1000.0), None);
+    clock.set(START + 600.0);
+    assert_eq!(kill_switch.check(clock.now_secs(), 960.0), None);
+    clock.set(START + 1200.0);
+    assert_eq!(
+        kill_switch.check(clock.now_secs(), 940.0),
+        Some("daily loss of 6.00%".to_string())
+    );
+}
+
+#[test]
+fn daily_loss_resets_each_day() {
+    let clock = SimulatedClock::new(START);
+    let mut kill_switch = KillSwitch::new(KillSwitchConfig {
+        max_drawdown_pct: 50.0,
+        ..config()
+    });
+
+    assert_eq!(kill_switch.check(clock.now_secs(), 1000.0), None);
+    clock.set(START + 600.0);
+    assert_eq!(kill_switch.check(clock.now_secs(), 960.0), None);
+    clock.set(START + DAY as f64);
+    assert_eq!(kill_switch.check(clock.now_secs(), 930.0), None);
+}
+
+#[test]
+fn trips_on_drawdown_across_days() {
+    let clock = SimulatedClock::new(START);
+    let mut kill_switch = KillSwitch::new(config());
+
+    // Each day loses less than 5%, but 12% from the peak in total
+    for (day, value) in [1000.0, 960.0, 920.0].iter().enumerate() {
+        clock.set(START + (day as u64 * DAY) as f64);
+        assert_eq!(kill_switch.check(clock.now_secs(), *value), None);
+    }
+    clock.set(START + (3 * DAY) as f64);
+    assert_eq!(
+        kill_switch.check(clock.now_secs(), 880.0),
+        Some("drawdown of 12.00%".to_string())
+    );
+}
+
+#[test]
+fn trips_on_fill_rate() {
+    let clock = SimulatedClock::new(START);
+    let mut kill_switch = KillSwitch::new(config());
+
+    for i in 0..3 {
+        clock.set(START + i as f64 * 60.0);
+        kill_switch.record_fill(clock.now_secs());
+    }
+    assert_eq!(kill_switch.check(clock.now_secs(), 1000.0), None);
+
+    // The first fill has left the hour window by the time the fourth arrives
+    clock.set(START + 3601.0);
+    kill_switch.record_fill(clock.now_secs());
+    assert_eq!(kill_switch.check(clock.now_secs(), 1000.0), None);
+
+    clock.set(START + 3660.0);
+    kill_switch.record_fill(clock.now_secs());
+    assert_eq!(
+        kill_switch.check(clock.now_secs(), 1000.0),
+        Some("4 fills in the last hour".to_string())
+    );
+}
+
+#[test]
+fn stays_tripped_until_reset() {
+    let clock = SimulatedClock::new(START);
+    let mut kill_switch = KillSwitch::new(config());
+    kill_switch.check(clock.now_secs(), 1000.0);
+    let reason = kill_switch.check(clock.now_secs(), 900.0).unwrap();
+    kill_switch.trip(reason);
+
+    // Recovering doesn't re-enable trading
+    assert_eq!(kill_switch.check(clock.now_secs(), 1000.0), None);
+    assert!(kill_switch.is_tripped());
+
+    // Losses are measured from the value after the reset
+    kill_switch.reset();
+    assert!(!kill_switch.is_tripped());
+    assert_eq!(kill_switch.check(clock.now_secs(), 900.0), None);
+}
+
+#[tokio::test]
+async fn trip_persists_through_journal() {
+    let clock = Arc::new(SimulatedClock::new(START));
+    let shared: SharedClock = clock.clone();
+    let path = std::env::temp_dir().join(format!("journal-{}.jsonl", uuid::Uuid::new_v4()));
+    let path = path.to_str().unwrap().to_string();
+    let journal = Arc::new(Mutex::new(Journal::open(&path, shared.clone()).unwrap()));
+
+    // The portfolio has halved since the switch last saw it
+    let mut controls = Controls::new(config());
+    controls.kill_switch.check(clock.now_secs(), 2000.0);
+    let controls = Arc::new(Mutex::new(controls));
+    let assets = HashMap::from([("ZUSD".to_string(), (1000.0, 1.0))]);
+    let portfolio = Arc::new(Mutex::new(Portfolio::from_assets(assets, shared.clone())));
+    let markets: Markets = Arc::new(Mutex::new(HashMap::new()));
+    let monitor = tokio::spawn(control::monitor_kill_switch(
+        portfolio,
+        controls.clone(),
+        markets,
+        journal.clone(),
+        shared.clone(),
+    ));
+
+    let mut tripped = false;
+    for _ in 0..100 {
+        if journal.lock().await.get_kill_switch().is_some() {
+            tripped = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    monitor.abort();
+    assert!(tripped, "Kill switch didn't trip");
+    assert!(controls.lock().await.kill_switch.is_tripped());
+
+    // A restart replays the trip, and a reset clears it
+    let reopened = Journal::open(&path, shared.clone()).unwrap();
+    assert_eq!(reopened.get_kill_switch(), Some("daily loss of 50.00%"));
+    clock.set(START + 60.0);
+    journal.lock().await.append(Event::KillSwitchReset {
+        by: "test".to_string(),
+    });
+    let reopened = Journal::open(&path, shared).unwrap();
+    assert_eq!(reopened.get_kill_switch(), None);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,99 @@
+use rebalancer::config::{LadderConfig, Spacing};
+use rebalancer::strategy::{Ladder, Quote, Side};
+
+fn quotes() -> Vec<Quote> {
+    vec![
+        Quote {
+            side: Side::Buy,
+            price: 2000.0,
+            size: 0.01,
+        },
+        Quote {
+            side: Side::Sell,
+            price: 2010.0,
+            size: 0.01,
+        },
+    ]
+}
+
+/// Returns the (side, price, size) of each rung, rounded to hide float noise.
+fn rungs(quotes: &[Quote]) -> Vec<(Side, f64, f64)> {
+    let round = |x: f64, decimals: i32| (x * 10f64.powi(decimals)).round() / 10f64.powi(decimals);
+    quotes
+        .iter()
+        .map(|q| (q.side, round(q.price, 6), round(q.size, 8)))
+        .collect()
+}
+
+#[test]
+fn spaces_rungs_in_bps() {
+    let ladder = Ladder::new(LadderConfig {
+        levels: 3,
+        spacing: Spacing::Bps(10.0),
+        size_scale: 1.0,
+    });
+
+    assert_eq!(
+        rungs(&ladder.expand(quotes(), None)),
+        vec![
+            (Side::Buy, 2000.0, 0.01),
+            (Side::Buy, 1998.0, 0.01),
+            (Side::Buy, 1996.0, 0.01),
+            (Side::Sell, 2010.0, 0.01),
+            (Side::Sell, 2012.01, 0.01),
+            (Side::Sell, 2014.02, 0.01),
+        ]
+    );
+}
+
+#[test]
+fn spaces_rungs_by_volatility() {
+    let ladder = Ladder::new(LadderConfig {
+        levels: 3,
+        spacing: Spacing::Volatility(2.0),
+        size_scale: 1.0,
+    });
+
+    // Two times 0.1% volatility is 20 bps between rungs
+    assert_eq!(
+        rungs(&ladder.expand(quotes(), Some(0.001))),
+        vec![
+            (Side::Buy, 2000.0, 0.01),
+            (Side::Buy, 1996.0, 0.01),
+            (Side::Buy, 1992.0, 0.01),
+            (Side::Sell, 2010.0, 0.01),
+            (Side::Sell, 2014.02, 0.01),
+            (Side::Sell, 2018.04, 0.01),
+        ]
+    );
+
+    // Without an estimate only the strategy's own quotes are placed
+    assert_eq!(rungs(&ladder.expand(quotes(), None)), rungs(&quotes()));
+}
+
+#[test]
+fn scales_sizes_geometrically() {
+    let ladder = Ladder::new(LadderConfig {
+        levels: 4,
+        spacing: Spacing::Bps(5.0),
+        size_scale: 1.5,
+    });
+
+    let expanded = ladder.expand(quotes(), None);
+    let sizes: Vec<f64> = rungs(&expanded)
+        .iter()
+        .filter(|(side, _, _)| *side == Side::Buy)
+        .map(|(_, _, size)| *size)
+        .collect();
+    assert_eq!(sizes, vec![0.01, 0.015, 0.0225, 0.03375]);
+    assert_eq!(rungs(&expanded)[3], (Side::Buy, 1997.0, 0.03375));
+}
+
+#[test]
+fn single_level_keeps_quotes() {
+    let ladder = Ladder::new(LadderConfig::default());
+    assert_eq!(
+        rungs(&ladder.expand(quotes(), Some(0.001))),
+        rungs(&quotes())
+    );
+}
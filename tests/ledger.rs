@@ -0,0 +1,211 @@
+use rebalancer::clock::{SharedClock, SimulatedClock};
+use rebalancer::journal::{Entry, Event, Journal};
+use rebalancer::ledger::{Ledger, LotMethod, Lots};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const JUNE_2023: u64 = 1_685_577_600;
+const SEPTEMBER_2023: u64 = 1_693_526_400;
+const FEBRUARY_2024: u64 = 1_706_745_600;
+
+fn balances(time: u64, eth: f64, eth_price: f64) -> Entry {
+    Entry {
+        time,
+        event: Event::Balances {
+            assets: HashMap::from([
+                ("ZUSD".to_string(), (10_000.0, 1.0)),
+                ("ETH".to_string(), (eth, eth_price)),
+            ]),
+        },
+    }
+}
+
+fn fill(time: u64, side: &str, volume: f64, price: f64, fee: f64) -> Entry {
+    Entry {
+        time,
+        event: Event::Fill {
+            pair: "ETH/USD".to_string(),
+            order_id: format!("O{}", time),
+            side: side.to_string(),
+            price,
+            volume,
+            fee,
+        },
+    }
+}
+
+/// Lots of 1 ETH at 1000 (held at start), 3000 and 2000, then a sale of 1.5 at 2500.
+fn entries() -> Vec<Entry> {
+    vec![
+        balances(JUNE_2023, 1.0, 1000.0),
+        fill(JUNE_2023 + 60, "buy", 1.0, 3000.0, 0.0),
+        fill(JUNE_2023 + 120, "buy", 1.0, 2000.0, 0.0),
+        fill(FEBRUARY_2024, "sell", 1.5, 2500.0, 0.0),
+    ]
+}
+
+/// Returns (acquired, volume, gain) for each lot the sale matched.
+fn sale_matches(method: LotMethod) -> Vec<(Option<u64>, f64, f64)> {
+    let ledger = Ledger::from_entries(method, &entries());
+    let sale = ledger.get_trades().last().unwrap();
+    assert_eq!(sale.side, "sell");
+    sale.matches
+        .iter()
+        .map(|m| (m.acquired, m.volume, m.gain))
+        .collect()
+}
+
+#[test]
+fn fifo_matches_oldest_lots() {
+    assert_eq!(
+        sale_matches(LotMethod::Fifo),
+        vec![
+            (Some(JUNE_2023), 1.0, 1500.0),
+            (Some(JUNE_2023 + 60), 0.5, -250.0)
+        ]
+    );
+}
+
+#[test]
+fn lifo_matches_newest_lots() {
+    assert_eq!(
+        sale_matches(LotMethod::Lifo),
+        vec![
+            (Some(JUNE_2023 + 120), 1.0, 500.0),
+            (Some(JUNE_2023 + 60), 0.5, -250.0)
+        ]
+    );
+}
+
+#[test]
+fn hifo_matches_costliest_lots() {
+    assert_eq!(
+        sale_matches(LotMethod::Hifo),
+        vec![
+            (Some(JUNE_2023 + 60), 1.0, -500.0),
+            (Some(JUNE_2023 + 120), 0.5, 250.0)
+        ]
+    );
+}
+
+#[test]
+fn totals_gains_per_year_net_of_fees() {
+    let mut entries = entries();
+    // 0.2 at 1500 less a 1 USD fee, against the 1000 lot
+    entries.insert(3, fill(SEPTEMBER_2023, "sell", 0.2, 1500.0, 1.0));
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+    let years = ledger.gains_by_year();
+
+    assert_eq!(years.len(), 2);
+    let gains_2023 = years[&2023];
+    assert!((gains_2023.proceeds - 299.0).abs() < 1e-9);
+    assert!((gains_2023.cost - 200.0).abs() < 1e-9);
+    assert!((gains_2023.gain - 99.0).abs() < 1e-9);
+    // The rest of the 1000 lot, then 0.7 of the 3000 lot
+    let gains_2024 = years[&2024];
+    assert!((gains_2024.proceeds - 3750.0).abs() < 1e-9);
+    assert!((gains_2024.cost - 2900.0).abs() < 1e-9);
+    assert!((gains_2024.gain - 850.0).abs() < 1e-9);
+}
+
+#[test]
+fn buy_fees_are_part_of_lot_cost() {
+    let entries = vec![
+        balances(JUNE_2023, 0.0, 1000.0),
+        fill(JUNE_2023 + 60, "buy", 1.0, 1000.0, 2.0),
+        fill(JUNE_2023 + 120, "sell", 1.0, 1100.0, 2.0),
+    ];
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+    let sale = &ledger.get_trades()[1];
+    assert_eq!(sale.matches.len(), 1);
+    assert_eq!(sale.matches[0].cost, 1002.0);
+    assert_eq!(sale.matches[0].proceeds, 1098.0);
+    assert_eq!(sale.matches[0].gain, 96.0);
+}
+
+#[test]
+fn ignores_fills_before_first_priced_balances() {
+    let entries = vec![
+        balances(JUNE_2023, 1.0, 0.0),
+        fill(JUNE_2023 + 60, "sell", 0.5, 1000.0, 1.0),
+        balances(JUNE_2023 + 120, 0.5, 1000.0),
+        fill(JUNE_2023 + 180, "sell", 0.5, 1200.0, 0.0),
+    ];
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+
+    // The early sale is part of the starting holdings rather than an unmatched gain
+    let trades = ledger.get_trades();
+    assert_eq!(trades.len(), 1);
+    assert_eq!(trades[0].matches.len(), 1);
+    assert_eq!(trades[0].matches[0].acquired, Some(JUNE_2023 + 120));
+    assert_eq!(trades[0].matches[0].gain, 100.0);
+}
+
+#[test]
+fn skips_zero_volume_fills() {
+    let entries = vec![
+        balances(JUNE_2023, 1.0, 1000.0),
+        fill(JUNE_2023 + 60, "buy", 0.0, 1000.0, 0.5),
+        fill(JUNE_2023 + 120, "sell", 0.0, 1000.0, 0.5),
+    ];
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+    assert!(ledger.get_trades().is_empty());
+    assert!(ledger.gains_by_year().is_empty());
+}
+
+#[test]
+fn agrees_with_pnl() {
+    let mut entries = entries();
+    entries.insert(3, fill(SEPTEMBER_2023, "sell", 0.2, 1500.0, 1.0));
+    entries.push(fill(FEBRUARY_2024 + 60, "buy", 0.4, 2400.0, 1.5));
+    let clock = Arc::new(SimulatedClock::new(0.0));
+    let shared: SharedClock = clock.clone();
+    let mut journal = Journal::memory(shared);
+    for entry in entries.iter() {
+        clock.set(entry.time as f64);
+        journal.append(entry.event.clone());
+    }
+
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+    let gain: f64 = ledger.gains_by_year().values().map(|year| year.gain).sum();
+    let assets = HashMap::from([("ETH".to_string(), (1.7, 2500.0))]);
+    let report = journal.get_pnl().report(&assets);
+    assert!((report.realized - gain).abs() < 1e-9);
+}
+
+#[test]
+fn partial_fills_leave_no_dust() {
+    // 0.1 - 0.07 is slightly more than 0.03 in floating point
+    let mut lots = Lots::default();
+    lots.buy(JUNE_2023, 0.07, 1000.0, 0.0);
+    lots.buy(JUNE_2023 + 60, 0.03, 1000.0, 0.0);
+    let matches = lots.sell(LotMethod::Fifo, 0.1, 1100.0, 0.0);
+    assert_eq!(matches.len(), 2);
+    assert!(matches.iter().all(|m| m.acquired.is_some()));
+    assert_eq!(lots.volume(), 0.0);
+
+    let mut lots = Lots::default();
+    lots.buy(JUNE_2023, 0.1, 1000.0, 0.0);
+    lots.sell(LotMethod::Fifo, 0.07, 1100.0, 0.0);
+    let matches = lots.sell(LotMethod::Fifo, 0.03, 1100.0, 0.0);
+    assert_eq!(matches.len(), 1);
+    assert_eq!(lots.volume(), 0.0);
+}
+
+#[test]
+fn trades_csv_fees_sum_to_fees_paid() {
+    let mut entries = entries();
+    entries[1] = fill(JUNE_2023 + 60, "buy", 1.0, 3000.0, 4.0);
+    entries[3] = fill(FEBRUARY_2024, "sell", 1.5, 2500.0, 6.0);
+    let ledger = Ledger::from_entries(LotMethod::Fifo, &entries);
+    let mut out = Vec::new();
+    ledger.write_trades_csv(&mut out).unwrap();
+
+    let mut csv = csv::Reader::from_reader(out.as_slice());
+    let fees: Vec<f64> = csv
+        .records()
+        .map(|record| record.unwrap()[5].parse().unwrap())
+        .collect();
+    // One row for each buy and one per lot of the sale, split 1 to 0.5 by volume
+    assert_eq!(fees, vec![4.0, 0.0, 4.0, 2.0]);
+}
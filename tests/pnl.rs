@@ -0,0 +1,74 @@
+use rebalancer::clock::{SharedClock, SimulatedClock};
+use rebalancer::journal::{Event, Journal};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const START: f64 = 1_700_000_000.0;
+
+fn balances(usd: f64, eth: f64, eth_price: f64) -> Event {
+    Event::Balances {
+        assets: HashMap::from([
+            ("ZUSD".to_string(), (usd, 1.0)),
+            ("ETH".to_string(), (eth, eth_price)),
+        ]),
+    }
+}
+
+fn fill(side: &str, volume: f64, price: f64, fee: f64) -> Event {
+    Event::Fill {
+        pair: "ETH/USD".to_string(),
+        order_id: "O1".to_string(),
+        side: side.to_string(),
+        price,
+        volume,
+        fee,
+    }
+}
+
+#[test]
+fn ignores_fills_before_tracking_starts() {
+    let clock: SharedClock = Arc::new(SimulatedClock::new(START));
+    let mut journal = Journal::memory(clock);
+
+    // Unpriced at startup, the fill happens before the first priced snapshot
+    journal.append(balances(2000.0, 1.0, 0.0));
+    journal.append(fill("buy", 0.5, 2000.0, 1.0));
+    journal.append(balances(999.0, 1.5, 2000.0));
+
+    let assets = journal.get_balances().unwrap().clone();
+    let report = journal.get_pnl().report(&assets);
+    let eth = &report.assets[0];
+    assert_eq!(eth.asset, "ETH");
+    assert_eq!(eth.amount, 1.5);
+    assert_eq!(eth.fifo_cost, 3000.0);
+    assert_eq!(eth.unrealized, 0.0);
+    assert_eq!(report.fees, 0.0);
+}
+
+#[test]
+fn tracks_fills_after_start() {
+    let clock: SharedClock = Arc::new(SimulatedClock::new(START));
+    let mut journal = Journal::memory(clock);
+
+    journal.append(balances(2000.0, 1.0, 2000.0));
+    journal.append(fill("buy", 0.5, 1900.0, 1.0));
+    journal.append(fill("sell", 1.0, 2100.0, 2.0));
+
+    let assets = HashMap::from([
+        (
+            "ZUSD".to_string(),
+            (2000.0 - 950.0 - 1.0 + 2100.0 - 2.0, 1.0),
+        ),
+        ("ETH".to_string(), (0.5, 2100.0)),
+    ]);
+    let report = journal.get_pnl().report(&assets);
+    let eth = &report.assets[0];
+    // Fees are part of the lot costs and sale proceeds
+    assert_eq!(eth.amount, 0.5);
+    assert_eq!(eth.realized_fifo, 98.0);
+    assert_eq!(eth.fifo_cost, 951.0);
+    assert_eq!(eth.unrealized, 99.0);
+    assert!((eth.realized_average - (2098.0 - 2951.0 / 1.5)).abs() < 1e-9);
+    assert_eq!(report.fees, 3.0);
+    assert_eq!(report.total, 197.0);
+}
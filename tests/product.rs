@@ -0,0 +1,158 @@
+use rebalancer::account::Portfolio;
+use rebalancer::clock::{SharedClock, SimulatedClock};
+use rebalancer::config::{AvellanedaStoikovConfig, KillSwitchConfig, PairConfig, StrategyConfig};
+use rebalancer::control::Controls;
+use rebalancer::history::History;
+use rebalancer::journal::{Event, Journal, MarketState};
+use rebalancer::product::Market;
+use rebalancer::volatility::Bar;
+use rebalancer::websocket::Sender;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Mutex;
+
+const START: f64 = 1_700_000_000.0;
+
+/// Builds an ETH/USD market holding ETH below its target, capturing what it sends.
+fn market(
+    config: PairConfig,
+    journal: Arc<Mutex<Journal>>,
+    clock: SharedClock,
+) -> (Market, Arc<Mutex<Portfolio>>, UnboundedReceiver<String>) {
+    let assets = HashMap::from([
+        ("ZUSD".to_string(), (1000.0, 1.0)),
+        ("ETH".to_string(), (0.1, 1900.0)),
+    ]);
+    let portfolio = Arc::new(Mutex::new(Portfolio::from_assets(assets, clock.clone())));
+    let (pub_sink, _) = Sender::capture();
+    let (priv_sink, captured) = Sender::capture();
+    let market = Market::new(
+        config,
+        portfolio.clone(),
+        journal,
+        Arc::new(Mutex::new(Controls::new(KillSwitchConfig::default()))),
+        pub_sink,
+        priv_sink,
+        "token".to_string(),
+        clock,
+    );
+    (market, portfolio, captured)
+}
+
+/// An hour of 1 minute bars ending at START, alternating between 2000 and 2002.
+fn history() -> History {
+    let bars = (0..60)
+        .map(|i| {
+            let close: f64 = if i % 2 == 0 { 2002.0 } else { 2000.0 };
+            Bar {
+                time: START - (59 - i) as f64 * 60.0,
+                open: 2001.0,
+                high: close.max(2001.0),
+                low: close.min(2001.0),
+                close,
+            }
+        })
+        .collect();
+    let spreads = (0..60)
+        .map(|i| (START - (59 - i) as f64 * 10.0, 1999.9, 2000.1))
+        .collect();
+    History {
+        bars,
+        spreads,
+        order_min: 0.002,
+    }
+}
+
+fn ticker() -> String {
+    json!([
+        1,
+        {
+            "a": ["2000.10", 1, "1.0"],
+            "b": ["1999.90", 1, "1.0"],
+            "c": ["2000.00", "0.1"],
+            "v": ["100.0", "1000.0"],
+            "p": ["2000.0", "2000.0"],
+            "t": [10, 100],
+            "l": ["1999.90", "1999.90"],
+            "h": ["2000.10", "2000.10"],
+            "o": ["2000.0", "2000.0"],
+        },
+        "ticker",
+        "ETH/USD"
+    ])
+    .to_string()
+}
+
+/// Returns the addOrder requests sent since the last call.
+fn add_orders(captured: &mut UnboundedReceiver<String>) -> Vec<Value> {
+    let mut orders = Vec::new();
+    while let Ok(message) = captured.try_recv() {
+        let message: Value = serde_json::from_str(&message).unwrap();
+        if message["event"] == "addOrder" {
+            orders.push(message);
+        }
+    }
+    orders
+}
+
+#[tokio::test]
+async fn warm_start_fills_price_and_volatility_buffers() {
+    let mut config = PairConfig::new("ETH/USD");
+    config.strategy = StrategyConfig::AvellanedaStoikov(AvellanedaStoikovConfig::default());
+    let clock: SharedClock = Arc::new(SimulatedClock::new(START));
+    let (mut market, portfolio, mut captured) = market(
+        config,
+        Arc::new(Mutex::new(Journal::memory(clock.clone()))),
+        clock,
+    );
+
+    // Without a price or volatility estimate the strategy has nothing to quote
+    market.rebalance().await;
+    assert!(!market.is_ready());
+    assert!(add_orders(&mut captured).is_empty());
+
+    market.warm_start(history()).await;
+    assert!(market.is_ready());
+    assert_eq!(
+        portfolio.lock().await.get_pair("ETH/USD".to_string()).1,
+        2000.0
+    );
+    market.rebalance().await;
+    let orders = add_orders(&mut captured);
+    let sides: Vec<&str> = orders.iter().map(|o| o["type"].as_str().unwrap()).collect();
+    assert_eq!(sides, vec!["buy", "sell"]);
+}
+
+#[tokio::test]
+async fn restores_journaled_market_state() {
+    let clock = Arc::new(SimulatedClock::new(START));
+    let mut journal = Journal::memory(clock.clone());
+    journal.append(Event::MarketState(MarketState {
+        pair: "ETH/USD".to_string(),
+        last_price: 1990.0,
+        last_order_time: START as u64 - 60,
+        strategy: None,
+    }));
+    let mut config = PairConfig::new("ETH/USD");
+    config.order_cooldown = 300;
+    let journal = Arc::new(Mutex::new(journal));
+    let (mut market, _, mut captured) = market(config, journal.clone(), clock.clone());
+    market.warm_start(history()).await;
+    market.restore().await;
+
+    // The order placed a minute before the restart still holds off the next one
+    market.on_message(ticker()).await;
+    assert!(add_orders(&mut captured).is_empty());
+
+    clock.set(START + 240.0);
+    market.on_message(ticker()).await;
+    assert_eq!(add_orders(&mut captured).len(), 1);
+
+    // The state journaled with the order carries the restored price forward
+    let journal = journal.lock().await;
+    let state = journal.get_market_state("ETH/USD").unwrap();
+    assert_eq!(state.last_price, 1990.0);
+    assert_eq!(state.last_order_time, START as u64 + 240);
+}
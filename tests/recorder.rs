@@ -0,0 +1,81 @@
+use rebalancer::config::RecorderConfig;
+use rebalancer::recorder::{Direction, Recorder, Tap};
+use rebalancer::replay;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+const TOKEN: &str = "ws-token-secret";
+
+/// Returns a recorder writing to a new temporary directory.
+fn recorder() -> (Arc<Mutex<Recorder>>, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("recordings-{}", uuid::Uuid::new_v4()));
+    let config = RecorderConfig {
+        dir: dir.to_str().unwrap().to_string(),
+        max_file_size_mb: 100,
+    };
+    (Arc::new(Mutex::new(Recorder::new(config))), dir)
+}
+
+fn recordings(dir: &PathBuf) -> Vec<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path().to_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn redacts_private_token() {
+    let (recorder, dir) = recorder();
+    let tap = Tap::new(recorder.clone(), "ETH/USD", "private");
+    let subscribe = json!({
+        "event": "subscribe",
+        "subscription": { "name": "openOrders", "token": TOKEN },
+    });
+    let order = json!({
+        "event": "addOrder",
+        "ordertype": "limit",
+        "pair": "ETH/USD",
+        "price": "2000.0",
+        "token": TOKEN,
+        "type": "buy",
+        "volume": "0.01",
+    });
+    tap.record(Direction::Outbound, &subscribe.to_string())
+        .await;
+    tap.record(Direction::Outbound, &order.to_string()).await;
+    tap.record(Direction::Inbound, r#"{"event":"heartbeat"}"#)
+        .await;
+    recorder.lock().await.finish();
+
+    let frames = replay::read_frames(&recordings(&dir)).unwrap();
+    assert_eq!(frames.len(), 3);
+    for frame in frames.iter() {
+        assert!(!frame.message.contains(TOKEN));
+    }
+    let subscribe: Value = serde_json::from_str(&frames[0].message).unwrap();
+    assert_eq!(subscribe["subscription"]["token"], "<redacted>");
+    assert_eq!(subscribe["subscription"]["name"], "openOrders");
+    let order: Value = serde_json::from_str(&frames[1].message).unwrap();
+    assert_eq!(order["token"], "<redacted>");
+    assert_eq!(order["volume"], "0.01");
+    assert_eq!(frames[2].message, r#"{"event":"heartbeat"}"#);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn frames_are_readable_after_flush() {
+    let (recorder, dir) = recorder();
+    let tap = Tap::new(recorder.clone(), "ETH/USD", "public");
+    tap.record(Direction::Inbound, r#"{"event":"heartbeat"}"#)
+        .await;
+    recorder.lock().await.flush();
+
+    // The file isn't finished, as after a crash
+    let frames = replay::read_frames(&recordings(&dir)).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].pair, "ETH/USD");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,140 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rebalancer::config::{KillSwitchConfig, PairConfig};
+use rebalancer::recorder::{Direction, Frame};
+use rebalancer::replay;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+
+const START: f64 = 1_700_000_000.0;
+
+fn ticker(bid: &str, ask: &str) -> String {
+    json!([
+        1,
+        {
+            "a": [ask, 1, "1.0"],
+            "b": [bid, 1, "1.0"],
+            "c": [bid, "0.1"],
+            "v": ["100.0", "1000.0"],
+            "p": [bid, bid],
+            "t": [10, 100],
+            "l": [bid, bid],
+            "h": [ask, ask],
+            "o": [bid, bid],
+        },
+        "ticker",
+        "ETH/USD"
+    ])
+    .to_string()
+}
+
+fn frame(time: f64, message: String) -> Frame {
+    Frame {
+        time,
+        connection: "public-1".to_string(),
+        pair: "ETH/USD".to_string(),
+        channel: "public".to_string(),
+        direction: Direction::Inbound,
+        message,
+    }
+}
+
+/// A book snapshot whose asks start below the quote, so a buy at 1998.5 would take them.
+fn crossed_book() -> String {
+    let levels = |start: f64, step: f64| -> Vec<Value> {
+        (0..10)
+            .map(|i| {
+                json!([
+                    format!("{:.2}", start + step * i as f64),
+                    "1.00000000",
+                    "1700000000.000000"
+                ])
+            })
+            .collect()
+    };
+    json!([3, { "as": levels(1998.0, 0.1), "bs": levels(1997.9, -0.1) }, "book-10", "ETH/USD"])
+        .to_string()
+}
+
+/// Writes the frames to a gzipped recording, out of order as across rotated files.
+fn record(frames: &[Frame]) -> String {
+    let path = std::env::temp_dir().join(format!("recording-{}.jsonl.gz", uuid::Uuid::new_v4()));
+    let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+    for frame in frames.iter().rev() {
+        writeln!(encoder, "{}", serde_json::to_string(frame).unwrap()).unwrap();
+    }
+    encoder.finish().unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn replays_recording_on_simulated_clock() {
+    let mut config = PairConfig::new("ETH/USD");
+    config.order_cooldown = 300;
+    // ETH is 200 of 1200 USD, well below its 50% target
+    let assets = HashMap::from([
+        ("ZUSD".to_string(), (1000.0, 1.0)),
+        ("ETH".to_string(), (0.1, 2000.0)),
+    ]);
+    let path = record(&[
+        frame(START, ticker("1999.90", "2000.10")),
+        // Within the order cooldown, so nothing is placed
+        frame(START + 60.0, ticker("1999.90", "2000.10")),
+        // Nothing acknowledged the first order, so it is placed again once the cooldown ends
+        frame(START + 300.0, ticker("1999.90", "2000.10")),
+    ]);
+
+    let frames = replay::read_frames(std::slice::from_ref(&path)).unwrap();
+    let captured = replay::run(config, &frames, assets, KillSwitchConfig::default(), None).await;
+    fs::remove_file(&path).unwrap();
+
+    let orders: Vec<(f64, Value)> = captured
+        .iter()
+        .map(|c| (c.time, serde_json::from_str(&c.message).unwrap()))
+        .filter(|(_, message): &(f64, Value)| message["event"] == "addOrder")
+        .collect();
+    assert!(captured.iter().all(|c| c.channel == "private"));
+    let order = json!({
+        "event": "addOrder",
+        "oflags": "post",
+        "ordertype": "limit",
+        "pair": "ETH/USD",
+        "price": "1998.5",
+        "token": "replay",
+        "type": "buy",
+        "volume": "0.015011258443832875",
+    });
+    assert_eq!(orders, vec![(START, order.clone()), (START + 300.0, order)]);
+}
+
+#[tokio::test]
+async fn skips_quotes_that_would_pay_taker_fees() {
+    let assets = HashMap::from([
+        ("ZUSD".to_string(), (1000.0, 1.0)),
+        ("ETH".to_string(), (0.1, 2000.0)),
+    ]);
+    let path = record(&[
+        frame(START, crossed_book()),
+        frame(START + 1.0, ticker("1999.90", "2000.10")),
+    ]);
+    let frames = replay::read_frames(std::slice::from_ref(&path)).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let add_orders = |post_only: bool| {
+        let mut config = PairConfig::new("ETH/USD");
+        config.orders.post_only = post_only;
+        let (frames, assets) = (frames.clone(), assets.clone());
+        async move {
+            replay::run(config, &frames, assets, KillSwitchConfig::default(), None)
+                .await
+                .iter()
+                .filter(|c| c.message.contains("addOrder"))
+                .count()
+        }
+    };
+    // Through the book the 0.075% edge can't pay a taker entry, sent post-only it rests as a maker
+    assert_eq!(add_orders(false).await, 0);
+    assert_eq!(add_orders(true).await, 1);
+}
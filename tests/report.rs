@@ -0,0 +1,98 @@
+use rebalancer::journal::{Entry, Event};
+use rebalancer::report::{self, REBALANCE_PERIOD};
+use std::collections::HashMap;
+
+const START: u64 = 1_700_000_000;
+const SNAPSHOT_INTERVAL: u64 = 300; // seconds
+
+fn balances(time: u64, usd: f64, eth: f64, eth_price: f64) -> Entry {
+    Entry {
+        time,
+        event: Event::Balances {
+            assets: HashMap::from([
+                ("ZUSD".to_string(), (usd, 1.0)),
+                ("ETH".to_string(), (eth, eth_price)),
+            ]),
+        },
+    }
+}
+
+/// Snapshots every SNAPSHOT_INTERVAL of 1000 USD and 1 ETH at each price.
+fn journal(prices: &[f64]) -> Vec<Entry> {
+    prices
+        .iter()
+        .enumerate()
+        .map(|(i, price)| balances(START + i as u64 * SNAPSHOT_INTERVAL, 1000.0, 1.0, *price))
+        .collect()
+}
+
+#[test]
+fn needs_snapshots_spanning_time() {
+    assert!(report::generate(&[], REBALANCE_PERIOD).is_none());
+    assert!(report::generate(&journal(&[1000.0]), REBALANCE_PERIOD).is_none());
+
+    let same_time = vec![
+        balances(START, 1000.0, 1.0, 1000.0),
+        balances(START, 1000.0, 1.0, 1100.0),
+    ];
+    assert!(report::generate(&same_time, REBALANCE_PERIOD).is_none());
+}
+
+#[test]
+fn measures_return_and_drawdown() {
+    // 2000 -> 2200 -> 1900 -> 2100
+    let entries = journal(&[1000.0, 1200.0, 900.0, 1100.0]);
+    let report = report::generate(&entries, REBALANCE_PERIOD).unwrap();
+
+    assert_eq!(report.snapshots, 4);
+    assert!((report.portfolio.total_return - 0.05).abs() < 1e-9);
+    assert!((report.portfolio.max_drawdown - 300.0 / 2200.0).abs() < 1e-9);
+    assert!(report.portfolio.sharpe.is_finite() && report.fee_drag.is_finite());
+    assert!(report.portfolio.volatility > 0.0);
+    // Nothing traded, so the portfolio is its own HODL benchmark
+    assert!((report.hodl.total_return - report.portfolio.total_return).abs() < 1e-9);
+    assert!(report.tracking_error_vs_hodl.abs() < 1e-9);
+}
+
+#[test]
+fn excludes_deposits_from_returns() {
+    let entries = vec![
+        balances(START, 1000.0, 1.0, 1000.0),
+        balances(START + SNAPSHOT_INTERVAL, 2000.0, 1.0, 1000.0),
+        balances(START + 2 * SNAPSHOT_INTERVAL, 2000.0, 1.0, 1300.0),
+    ];
+    let report = report::generate(&entries, REBALANCE_PERIOD).unwrap();
+    assert!((report.portfolio.total_return - 0.1).abs() < 1e-9);
+    assert_eq!(report.end_value, 3300.0);
+}
+
+#[test]
+fn extra_snapshots_do_not_change_risk() {
+    let prices = [1000.0, 1050.0, 990.0, 1020.0, 1100.0, 1040.0, 1080.0];
+    let regular = report::generate(&journal(&prices), REBALANCE_PERIOD).unwrap();
+
+    // Snapshots written after fills fall between the regular ones
+    let mut entries = journal(&prices);
+    for (i, price) in [(1, 1200.0), (3, 900.0), (4, 1150.0)] {
+        let time = START + i * SNAPSHOT_INTERVAL + 60;
+        entries.push(balances(time, 1000.0, 1.0, price));
+    }
+    entries.sort_by_key(|entry| entry.time);
+    let irregular = report::generate(&entries, REBALANCE_PERIOD).unwrap();
+
+    assert_eq!(irregular.snapshots, regular.snapshots + 3);
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+    assert!(close(
+        irregular.portfolio.volatility,
+        regular.portfolio.volatility
+    ));
+    assert!(close(irregular.portfolio.sharpe, regular.portfolio.sharpe));
+    assert!(close(
+        irregular.portfolio.sortino,
+        regular.portfolio.sortino
+    ));
+    assert!(close(
+        irregular.portfolio.total_return,
+        regular.portfolio.total_return
+    ));
+}
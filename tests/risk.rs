@@ -0,0 +1,182 @@
+use rebalancer::account::Portfolio;
+use rebalancer::clock::{SharedClock, SystemClock};
+use rebalancer::config::RiskConfig;
+use rebalancer::risk::{Rejection, RiskContext, RiskLimits};
+use rebalancer::strategy::{Quote, Side};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const MID: f64 = 2000.0;
+
+fn context() -> RiskContext {
+    RiskContext {
+        mid_price: MID,
+        amount: 1.0,
+        cash: 2000.0,
+        total_value: 4000.0,
+        open_orders: 0,
+        reserved_cash: 0.0,
+        reserved_amount: 0.0,
+        other_reserved_cash: 0.0,
+        order_min: 0.0,
+    }
+}
+
+fn quote(side: Side, size: f64) -> Quote {
+    Quote {
+        side,
+        price: MID,
+        size,
+    }
+}
+
+#[test]
+fn clips_to_max_order_notional() {
+    let limits = RiskLimits::new(RiskConfig {
+        max_order_notional: 100.0,
+        ..RiskConfig::default()
+    });
+
+    let checked = limits.check(&quote(Side::Buy, 1.0), &context()).unwrap();
+    assert_eq!(checked.size, 0.05);
+    let checked = limits.check(&quote(Side::Sell, 0.01), &context()).unwrap();
+    assert_eq!(checked.size, 0.01);
+}
+
+#[test]
+fn keeps_inventory_within_position_band() {
+    let limits = RiskLimits::new(RiskConfig {
+        max_order_notional: 10_000.0,
+        max_position_weight: Some(0.6),
+        ..RiskConfig::default()
+    });
+
+    // 2000 of 4000 USD held, so 400 USD of headroom to 60%
+    let checked = limits.check(&quote(Side::Buy, 1.0), &context()).unwrap();
+    assert!((checked.size - 0.2).abs() < 1e-9);
+
+    let mut ctx = context();
+    ctx.reserved_cash = 400.0;
+    assert!(matches!(
+        limits.check(&quote(Side::Buy, 0.1), &ctx),
+        Err(Rejection::MaxPosition { .. })
+    ));
+    // Selling reduces the position, so the band doesn't apply
+    assert!(limits.check(&quote(Side::Sell, 0.1), &ctx).is_ok());
+}
+
+#[test]
+fn keeps_cash_reserve_and_sells_only_held_asset() {
+    let limits = RiskLimits::new(RiskConfig {
+        max_order_notional: 10_000.0,
+        min_cash_reserve: 1500.0,
+        ..RiskConfig::default()
+    });
+
+    let checked = limits.check(&quote(Side::Buy, 1.0), &context()).unwrap();
+    assert_eq!(checked.size, 0.25);
+
+    let mut ctx = context();
+    ctx.reserved_cash = 500.0;
+    assert!(matches!(
+        limits.check(&quote(Side::Buy, 0.1), &ctx),
+        Err(Rejection::InsufficientCash { .. })
+    ));
+
+    ctx.reserved_amount = 0.75;
+    let checked = limits.check(&quote(Side::Sell, 1.0), &ctx).unwrap();
+    assert_eq!(checked.size, 0.25);
+    ctx.reserved_amount = 1.0;
+    assert!(matches!(
+        limits.check(&quote(Side::Sell, 0.1), &ctx),
+        Err(Rejection::InsufficientAsset { .. })
+    ));
+}
+
+#[test]
+fn limits_open_orders() {
+    let limits = RiskLimits::new(RiskConfig {
+        max_open_orders: 2,
+        ..RiskConfig::default()
+    });
+    let mut ctx = context();
+
+    for _ in 0..2 {
+        let checked = limits.check(&quote(Side::Buy, 0.01), &ctx).unwrap();
+        ctx.reserve(&checked);
+    }
+    assert_eq!(
+        limits.check(&quote(Side::Buy, 0.01), &ctx).unwrap_err(),
+        Rejection::TooManyOrders { open: 2 }
+    );
+}
+
+#[test]
+fn rejects_prices_outside_collar() {
+    let limits = RiskLimits::new(RiskConfig {
+        price_collar_bps: 100.0,
+        ..RiskConfig::default()
+    });
+    let mut far = quote(Side::Buy, 0.01);
+    far.price = MID * 0.95;
+
+    assert!(matches!(
+        limits.check(&far, &context()),
+        Err(Rejection::OutsideCollar { .. })
+    ));
+    let mut ctx = context();
+    ctx.mid_price = 0.0;
+    assert_eq!(
+        limits.check(&quote(Side::Buy, 0.01), &ctx).unwrap_err(),
+        Rejection::NoPrice
+    );
+}
+
+#[test]
+fn skips_orders_clipped_below_exchange_minimum() {
+    let limits = RiskLimits::new(RiskConfig {
+        max_order_notional: 10.0,
+        ..RiskConfig::default()
+    });
+    let mut ctx = context();
+    ctx.order_min = 0.002;
+
+    // 10 USD is 0.005, above the minimum
+    assert!(limits.check(&quote(Side::Buy, 1.0), &ctx).is_ok());
+
+    ctx.reserved_amount = 0.999;
+    assert_eq!(
+        limits.check(&quote(Side::Sell, 1.0), &ctx).unwrap_err(),
+        Rejection::BelowOrderMin {
+            size: 1.0 - 0.999,
+            order_min: 0.002
+        }
+    );
+}
+
+#[test]
+fn counts_cash_reserved_by_other_pairs() {
+    let clock: SharedClock = Arc::new(SystemClock);
+    let mut portfolio = Portfolio::from_assets(HashMap::new(), clock);
+    portfolio.set_reserved_cash("ETH/USD".to_string(), 300.0);
+    portfolio.set_reserved_cash("XBT/USD".to_string(), 1500.0);
+    portfolio.set_reserved_cash("SOL/USD".to_string(), 100.0);
+    assert_eq!(portfolio.get_reserved_cash_except("ETH/USD"), 1600.0);
+
+    let limits = RiskLimits::new(RiskConfig {
+        max_order_notional: 10_000.0,
+        ..RiskConfig::default()
+    });
+    let mut ctx = context();
+    ctx.reserved_cash = 300.0;
+    ctx.other_reserved_cash = portfolio.get_reserved_cash_except("ETH/USD");
+
+    // 2000 USD held, 1900 of it committed to bids across pairs
+    let checked = limits.check(&quote(Side::Buy, 1.0), &ctx).unwrap();
+    assert!((checked.size - 0.05).abs() < 1e-9);
+    ctx.other_reserved_cash = 1700.0;
+    assert!(matches!(
+        limits.check(&quote(Side::Buy, 0.01), &ctx),
+        Err(Rejection::InsufficientCash { .. })
+    ));
+}
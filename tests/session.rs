@@ -0,0 +1,335 @@
+mod support;
+
+use base64::{engine::general_purpose, Engine as _};
+use rebalancer::account::{Portfolio, Signer};
+use rebalancer::auth::Credentials;
+use rebalancer::clock::{Clock, SharedClock, SimulatedClock, SystemClock};
+use rebalancer::config::{Config, KillSwitchConfig};
+use rebalancer::control::{Controls, Markets};
+use rebalancer::fees;
+use rebalancer::journal::{Event, Journal};
+use rebalancer::product::Market;
+use rebalancer::task::{self, Context};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use support::mock_kraken::{wait_until, MockKraken, KEY, SECRET};
+use tokio::sync::Mutex;
+
+const PRICE: f64 = 2000.0;
+const PAIR: &str = r#"
+    pair = "ETH/USD"
+    strategy = { kind = "threshold", order_size_usd = 30.0, delta_threshold = 1.5 }
+"#;
+
+/// Starts the bot against the mock for a single ETH/USD pair and returns its context.
+async fn start_session(mock: &MockKraken) -> Context {
+    start(mock, PAIR, false, Arc::new(SystemClock)).await
+}
+
+/// Starts the bot with the given pair settings, with paper trading orders are kept from the
+/// mock. Requests are signed on the system clock, so nonces keep increasing whatever `clock`
+/// does.
+async fn start(mock: &MockKraken, pair: &str, paper: bool, clock: SharedClock) -> Context {
+    let config: Config = toml::from_str(&format!("[[pairs]]\n{}", pair)).unwrap();
+    let signer = Signer::new(
+        Credentials::new(KEY, SECRET, None).unwrap(),
+        mock.endpoints.rest.clone(),
+        Arc::new(SystemClock),
+    )
+    .await;
+    let signer = Arc::new(Mutex::new(signer));
+    let portfolio = Portfolio::new(signer.clone(), clock.clone()).await;
+    let markets: Markets = Arc::new(Mutex::new(HashMap::new()));
+    let context = Context {
+        portfolio: Arc::new(Mutex::new(portfolio)),
+        journal: Arc::new(Mutex::new(Journal::memory(clock.clone()))),
+        controls: Arc::new(Mutex::new(Controls::new(KillSwitchConfig::default()))),
+        markets,
+        signer: if paper { None } else { Some(signer) },
+        recorder: None,
+        endpoints: mock.endpoints.clone(),
+        cache_dir: std::env::temp_dir()
+            .join(format!("cache-{}", uuid::Uuid::new_v4()))
+            .to_str()
+            .unwrap()
+            .to_string(),
+        clock,
+    };
+    task::spawn(config.pairs[0].clone(), context.clone()).await;
+    context
+}
+
+/// Waits for the market to start, after warming up from the mock's history.
+async fn wait_for_market(context: &Context) -> Arc<Mutex<Market>> {
+    wait_until("market", || async {
+        context.markets.lock().await.contains_key("ETH/USD")
+    })
+    .await;
+    context.markets.lock().await["ETH/USD"].clone()
+}
+
+/// Waits for the market to see its order open on the exchange.
+async fn wait_for_open_order(context: &Context) -> Arc<Mutex<Market>> {
+    let market = wait_for_market(context).await;
+    wait_until("open order", || async {
+        !market.lock().await.get_orders().is_empty()
+    })
+    .await;
+    market
+}
+
+#[tokio::test]
+async fn buys_underweight_asset() {
+    // ETH is 200 of 1200 USD, well below its 50% target
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let _context = start_session(&mock).await;
+
+    let orders = mock.wait_for_orders(1).await;
+    let order = &orders[0];
+    assert_eq!(order["pair"], "ETH/USD");
+    assert_eq!(order["type"], "buy");
+    assert_eq!(order["ordertype"], "limit");
+    assert_eq!(order["oflags"], "post");
+    let price: f64 = order["price"].as_str().unwrap().parse().unwrap();
+    assert!(price < PRICE && price > PRICE * 0.99);
+    assert_eq!(mock.rejected_signatures().await, 0);
+}
+
+#[tokio::test]
+async fn fill_updates_portfolio() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start_session(&mock).await;
+
+    mock.wait_for_orders(1).await;
+    wait_for_open_order(&context).await;
+    // Snapshot the priced balances, as the periodic snapshot would, so profit is tracked
+    let assets = context.portfolio.lock().await.get_assets();
+    context
+        .journal
+        .lock()
+        .await
+        .append(Event::Balances { assets });
+    let txid = mock.open_orders().await.pop().unwrap();
+    mock.fill(&txid).await;
+
+    wait_until("fill", || async {
+        let (amount, _) = context
+            .portfolio
+            .lock()
+            .await
+            .get_pair("ETH/USD".to_string());
+        amount > 0.1
+    })
+    .await;
+    let pnl = context
+        .journal
+        .lock()
+        .await
+        .get_pnl()
+        .report(&context.portfolio.lock().await.get_assets());
+    assert!(pnl.fees > 0.0);
+}
+
+#[tokio::test]
+async fn holds_orders_until_exchange_online() {
+    for status in ["maintenance", "cancel_only"] {
+        let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+        mock.set_status(status).await;
+        let context = start_session(&mock).await;
+
+        // Several tickers arrive meanwhile, each of which would otherwise place the order
+        wait_for_market(&context).await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert!(mock.orders().await.is_empty(), "ordered during {}", status);
+
+        mock.set_status("online").await;
+        let orders = mock.wait_for_orders(1).await;
+        assert_eq!(orders[0]["type"], "buy");
+        assert_eq!(mock.open_orders().await.len(), 1);
+    }
+}
+
+#[tokio::test]
+async fn forces_post_only_during_post_only_status() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    mock.set_status("post_only").await;
+    let pair = format!("{}orders = {{ post_only = false }}", PAIR);
+    let context = start(&mock, &pair, false, Arc::new(SystemClock)).await;
+
+    let orders = mock.wait_for_orders(1).await;
+    assert_eq!(orders[0]["oflags"], "post");
+    wait_for_open_order(&context).await;
+}
+
+#[tokio::test]
+async fn requotes_soon_after_post_only_rejection() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    mock.cross_next_order().await;
+    let start_time = SystemClock.now();
+    let clock = Arc::new(SimulatedClock::new(start_time));
+    let _context = start(&mock, PAIR, false, clock.clone()).await;
+
+    // The rejection shortens the 300 second order cooldown to a 10 second retry
+    mock.wait_for_orders(1).await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    clock.set(start_time + 9.0);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(mock.orders().await.len(), 1);
+    assert!(mock.open_orders().await.is_empty());
+
+    clock.set(start_time + 10.0);
+    let orders = mock.wait_for_orders(2).await;
+    assert_eq!(orders[1]["oflags"], "post");
+    assert_eq!(mock.open_orders().await.len(), 1);
+}
+
+#[tokio::test]
+async fn sends_configured_order_flags() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let pair = format!(
+        "{}orders = {{ post_only = false, time_in_force = \"GTD\", expire_after = 60 }}",
+        PAIR
+    );
+    let _context = start(&mock, &pair, false, Arc::new(SystemClock)).await;
+
+    let orders = mock.wait_for_orders(1).await;
+    assert!(orders[0].get("oflags").is_none());
+    assert_eq!(orders[0]["timeinforce"], "GTD");
+    assert_eq!(orders[0]["expiretm"], "+60");
+}
+
+#[tokio::test]
+async fn quotes_with_fees_from_trade_volume() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start_session(&mock).await;
+    let market = wait_for_open_order(&context).await;
+    let orders = mock.orders().await;
+    assert_eq!(orders[0]["price"], "1998.5");
+
+    let signer = context.signer.clone().unwrap();
+    let pairs = vec!["ETH/USD".to_string()];
+    tokio::spawn(fees::refresh(context.portfolio.clone(), signer, pairs));
+    wait_until("fees", || async {
+        context.portfolio.lock().await.get_fees("ETH/USD").maker == 0.0025
+    })
+    .await;
+    assert_eq!(
+        context.portfolio.lock().await.get_fees("ETH/USD").taker,
+        0.004
+    );
+
+    // The 0.25% maker fee widens the quote to 0.375% below mid
+    market.lock().await.rebalance().await;
+    let orders = mock.wait_for_orders(2).await;
+    assert_eq!(orders[1]["price"], "1992.5");
+}
+
+#[tokio::test]
+async fn cancel_all_cancels_open_orders() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start_session(&mock).await;
+
+    mock.wait_for_orders(1).await;
+    let market = wait_for_open_order(&context).await;
+    market.lock().await.cancel_all_orders().await;
+
+    wait_until("cancel", || async {
+        market.lock().await.get_orders().is_empty()
+    })
+    .await;
+    assert_eq!(mock.cancels().await.len(), 1);
+    assert!(mock.open_orders().await.is_empty());
+}
+
+#[tokio::test]
+async fn cancels_all_orders_over_rest() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start_session(&mock).await;
+
+    mock.wait_for_orders(1).await;
+    let market = wait_for_open_order(&context).await;
+    let signer = context.signer.clone().unwrap();
+    let open = signer.lock().await.get_open_orders().await;
+    assert_eq!(open.as_object().unwrap().len(), 1);
+    assert_eq!(signer.lock().await.cancel_all_orders().await, Some(1));
+
+    assert!(mock.open_orders().await.is_empty());
+    wait_until("cancel", || async {
+        market.lock().await.get_orders().is_empty()
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn paper_trading_sends_no_orders() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start(&mock, PAIR, true, Arc::new(SystemClock)).await;
+
+    let market = wait_for_open_order(&context).await;
+    let orders = market.lock().await.get_orders();
+    assert_eq!(orders.len(), 1);
+    assert_eq!(orders[0].side, "buy");
+    assert!(mock.orders().await.is_empty());
+}
+
+#[tokio::test]
+async fn paper_order_fills_when_market_trades_through() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    let context = start(&mock, PAIR, true, Arc::new(SystemClock)).await;
+
+    wait_for_open_order(&context).await;
+    let assets = context.portfolio.lock().await.get_assets();
+    context
+        .journal
+        .lock()
+        .await
+        .append(Event::Balances { assets });
+    // The ask falls to 1997.80, below the paper bid at 1998.50
+    mock.set_price(1997.6).await;
+
+    wait_until("paper fill", || async {
+        let (amount, _) = context
+            .portfolio
+            .lock()
+            .await
+            .get_pair("ETH/USD".to_string());
+        amount > 0.1
+    })
+    .await;
+    let pnl = context
+        .journal
+        .lock()
+        .await
+        .get_pnl()
+        .report(&context.portfolio.lock().await.get_assets());
+    assert!(pnl.fees > 0.0);
+    assert!(mock.orders().await.is_empty());
+}
+
+#[tokio::test]
+async fn resubscribes_book_on_checksum_mismatch() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0), ("XETH", 0.1)], PRICE).await;
+    mock.corrupt_next_checksum().await;
+    let _context = start_session(&mock).await;
+
+    wait_until("book resubscription", || async {
+        mock.book_subscriptions().await == 2
+    })
+    .await;
+}
+
+#[tokio::test]
+async fn rejects_wrong_secret() {
+    let mock = MockKraken::start(&[("ZUSD", 1000.0)], PRICE).await;
+    let secret = general_purpose::STANDARD.encode([7u8; 64]);
+    let signer = Signer::new(
+        Credentials::new(KEY, &secret, None).unwrap(),
+        mock.endpoints.rest.clone(),
+        Arc::new(SystemClock),
+    )
+    .await;
+
+    assert!(signer.get_trade_fees("ETH/USD").await.is_none());
+    assert_eq!(mock.rejected_signatures().await, 1);
+}
@@ -0,0 +1,171 @@
+use rebalancer::book::OrderBook;
+use rebalancer::config::{AvellanedaStoikovConfig, ThresholdConfig};
+use rebalancer::fees::Fees;
+use rebalancer::messages::BookData;
+use rebalancer::strategy::{
+    AvellanedaStoikov, MarketSnapshot, PortfolioSnapshot, Quote, Side, Strategy, Threshold,
+};
+
+const MID: f64 = 2000.0;
+
+fn market() -> MarketSnapshot {
+    MarketSnapshot {
+        pair: "ETH/USD".to_string(),
+        mid_price: MID,
+        last_price: 0.0,
+        prices: vec![MID; 10],
+        spreads: vec![0.0005; 10],
+        vol_24hr: 1000.0,
+        volatility: Some(0.001),
+        book: None,
+        fees: Fees::default(),
+    }
+}
+
+fn portfolio(target_delta: f64) -> PortfolioSnapshot {
+    PortfolioSnapshot {
+        amount: 1.0,
+        price: MID,
+        total_value: 4000.0,
+        cash: 2000.0,
+        target_delta,
+    }
+}
+
+fn close(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+fn assert_quote(quote: &Quote, side: Side, price: f64, size: f64) {
+    assert_eq!(quote.side, side);
+    assert!(
+        close(quote.price, price),
+        "price {} != {}",
+        quote.price,
+        price
+    );
+    assert!(close(quote.size, size), "size {} != {}", quote.size, size);
+}
+
+#[test]
+fn threshold_quotes_towards_target() {
+    let mut strategy = Threshold::new(ThresholdConfig::default());
+
+    // The default 0.05% maker fee gives a 0.15% minimum spread, half of it on each side of mid
+    let quotes = strategy.quotes(&market(), &portfolio(2.0)).unwrap();
+    assert_eq!(quotes.len(), 1);
+    assert_quote(&quotes[0], Side::Buy, 1998.5, 30.0 / 1998.5);
+
+    let quotes = strategy.quotes(&market(), &portfolio(-2.0)).unwrap();
+    assert_eq!(quotes.len(), 1);
+    assert_quote(&quotes[0], Side::Sell, 2001.5, 30.0 / 2001.5);
+}
+
+#[test]
+fn threshold_waits_within_band_and_without_price() {
+    let mut strategy = Threshold::new(ThresholdConfig::default());
+
+    // Within 1.5% of the target nothing should rest, so live orders are cancelled
+    assert!(strategy
+        .quotes(&market(), &portfolio(1.0))
+        .unwrap()
+        .is_empty());
+    assert!(strategy.quotes(&market(), &portfolio(0.0)).is_none());
+    let mut no_price = market();
+    no_price.mid_price = 0.0;
+    assert!(strategy.quotes(&no_price, &portfolio(2.0)).is_none());
+}
+
+#[test]
+fn avellaneda_stoikov_skews_towards_target() {
+    let mut strategy = AvellanedaStoikov::new(AvellanedaStoikovConfig::default());
+    let mut market = market();
+    market.fees = Fees {
+        maker: 0.0,
+        taker: 0.0,
+    };
+
+    // Volatility of 0.1% plus the 0.05% base, so the reserve price moves by
+    // sqrt(4) * 15 * 0.0015^2 = 0.00675% and the spread is 15 * 0.0015^2 + ln(1 + 15 / 50) / 2000
+    let quotes = strategy.quotes(&market, &portfolio(4.0)).unwrap();
+    assert_eq!(quotes.len(), 2);
+    assert_quote(&quotes[0], Side::Buy, 1999.835068, 30.0 / 1999.835068);
+    assert_quote(&quotes[1], Side::Sell, 2000.299943, 30.0 / 2000.299943);
+
+    let quotes = strategy.quotes(&market, &portfolio(-4.0)).unwrap();
+    assert_quote(&quotes[0], Side::Buy, 1999.700079, 30.0 / 1999.700079);
+    assert_quote(&quotes[1], Side::Sell, 2000.164932, 30.0 / 2000.164932);
+}
+
+#[test]
+fn avellaneda_stoikov_keeps_fee_floor() {
+    let mut strategy = AvellanedaStoikov::new(AvellanedaStoikovConfig::default());
+
+    // The model spread is under 0.02%, below the 0.15% needed to cover fees
+    let quotes = strategy.quotes(&market(), &portfolio(4.0)).unwrap();
+    assert!(close(quotes[0].price, MID * (1.0 - 0.00075)));
+    assert!(close(quotes[1].price, 2000.135 * (1.0 + 0.00075)));
+}
+
+#[test]
+fn avellaneda_stoikov_needs_volatility_and_inventory() {
+    let mut strategy = AvellanedaStoikov::new(AvellanedaStoikovConfig::default());
+    assert!(strategy.quotes(&market(), &portfolio(0.0)).is_none());
+
+    let mut warming_up = market();
+    warming_up.volatility = None;
+    assert!(strategy.quotes(&warming_up, &portfolio(4.0)).is_none());
+}
+
+/// Ten levels 10 cents apart on each side of 2000, with volume thinning away from the touch.
+fn book() -> OrderBook {
+    let levels = |sign: f64| {
+        (1..=10)
+            .map(|i| {
+                vec![
+                    format!("{:.2}", MID + sign * 0.1 * i as f64),
+                    format!("{:.8}", 5.0 / i as f64),
+                    "1700000000.000000".to_string(),
+                ]
+            })
+            .collect()
+    };
+    let mut book = OrderBook::new();
+    book.apply(&BookData {
+        snapshot_asks: Some(levels(1.0)),
+        snapshot_bids: Some(levels(-1.0)),
+        asks: None,
+        bids: None,
+        checksum: None,
+    })
+    .unwrap();
+    book
+}
+
+#[test]
+fn book_density_is_on_the_configured_scale() {
+    let quoted_spread = |book_density: bool| {
+        let mut strategy = AvellanedaStoikov::new(AvellanedaStoikovConfig {
+            book_density,
+            ..AvellanedaStoikovConfig::default()
+        });
+        let mut market = market();
+        market.book = Some(book());
+        market.fees = Fees {
+            maker: 0.0,
+            taker: 0.0,
+        };
+        let quotes = strategy.quotes(&market, &portfolio(4.0)).unwrap();
+        quotes[1].price - quotes[0].price
+    };
+
+    let configured = quoted_spread(false);
+    let estimated = quoted_spread(true);
+    let ratio = estimated / configured;
+    assert!(
+        ratio > 0.5 && ratio < 2.0,
+        "{} vs {}",
+        estimated,
+        configured
+    );
+}
@@ -0,0 +1,646 @@
+//! A local stand-in for the Kraken REST and WebSocket APIs, so whole sessions can run offline.
+
+use axum::extract::{Path, Query, State};
+use axum::http::HeaderMap;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::{engine::general_purpose, Engine as _};
+use futures_util::{SinkExt, StreamExt};
+use hmac::{Hmac, Mac};
+use rebalancer::config::Endpoints;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio_tungstenite::tungstenite::Message;
+
+pub const KEY: &str = "mock-key";
+pub const SECRET: &str =
+    "kQH5HW/8p1uGOVjbgWA7FunAmGO8lsSUXNsu3eow76sz84Q18fWxnyRzBHCd3pd5nE9qa99HAZtuZuj6F1huXg==";
+const TOKEN: &str = "mock-token";
+const TICKER_INTERVAL: u64 = 200; // milliseconds
+
+type Outbox = mpsc::UnboundedSender<String>;
+
+struct Exchange {
+    secret: Vec<u8>,
+    last_nonce: u64,
+    balances: HashMap<String, f64>,
+    price: f64,
+    status: String,               // Reported by systemStatus
+    orders: Vec<Value>,           // addOrder requests received
+    open: HashMap<String, Value>, // txid -> addOrder request
+    cancels: Vec<String>,
+    rejected_signatures: usize,
+    next_txid: u64,
+    sequence: i64,
+    private_clients: Vec<Outbox>,
+    clients: Vec<Outbox>, // Every connection, public and private
+    book_subscriptions: usize,
+    corrupt_checksums: usize, // Book updates still to send with a wrong checksum
+    crossing_orders: usize,   // Post-only orders still to reject as if they crossed
+}
+
+impl Exchange {
+    /// Sends an openOrders update to every private connection.
+    fn push_orders(&mut self, orders: Value) {
+        self.sequence += 1;
+        let message = json!([[orders], "openOrders", { "sequence": self.sequence }]).to_string();
+        self.private_clients
+            .retain(|client| client.send(message.clone()).is_ok());
+    }
+}
+
+/// Handle to a running mock exchange.
+#[derive(Clone)]
+pub struct MockKraken {
+    pub endpoints: Endpoints,
+    exchange: Arc<Mutex<Exchange>>,
+}
+
+impl MockKraken {
+    /// Starts the REST and WebSocket servers on local ports.
+    ///
+    /// # Arguments
+    ///
+    /// * `balances` - Account balances by Kraken asset name, e.g. "XETH".
+    /// * `price` - Price quoted for every pair.
+    pub async fn start(balances: &[(&str, f64)], price: f64) -> Self {
+        let exchange = Arc::new(Mutex::new(Exchange {
+            secret: general_purpose::STANDARD.decode(SECRET).unwrap(),
+            last_nonce: 0,
+            balances: balances.iter().map(|(a, b)| (a.to_string(), *b)).collect(),
+            price,
+            status: "online".to_string(),
+            orders: Vec::new(),
+            open: HashMap::new(),
+            cancels: Vec::new(),
+            rejected_signatures: 0,
+            next_txid: 1,
+            sequence: 0,
+            private_clients: Vec::new(),
+            clients: Vec::new(),
+            book_subscriptions: 0,
+            corrupt_checksums: 0,
+            crossing_orders: 0,
+        }));
+
+        let app = Router::new()
+            .route("/0/public/OHLC", get(ohlc))
+            .route("/0/public/Spread", get(spread))
+            .route("/0/public/AssetPairs", get(asset_pairs))
+            .route("/0/private/:method", post(private))
+            .with_state(exchange.clone());
+        let rest = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rest_addr = rest.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(rest, app).await.unwrap() });
+
+        let public = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let public_addr = public.local_addr().unwrap();
+        tokio::spawn(accept(public, exchange.clone(), false));
+        let private = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let private_addr = private.local_addr().unwrap();
+        tokio::spawn(accept(private, exchange.clone(), true));
+
+        MockKraken {
+            endpoints: Endpoints {
+                rest: format!("http://{}", rest_addr),
+                public_ws: format!("ws://{}", public_addr),
+                private_ws: format!("ws://{}", private_addr),
+            },
+            exchange,
+        }
+    }
+
+    /// Returns the addOrder requests received so far.
+    pub async fn orders(&self) -> Vec<Value> {
+        self.exchange.lock().await.orders.clone()
+    }
+
+    /// Waits until at least `count` orders have been received.
+    pub async fn wait_for_orders(&self, count: usize) -> Vec<Value> {
+        for _ in 0..100 {
+            let orders = self.orders().await;
+            if orders.len() >= count {
+                return orders;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("Timed out waiting for {} orders", count);
+    }
+
+    pub async fn cancels(&self) -> Vec<String> {
+        self.exchange.lock().await.cancels.clone()
+    }
+
+    pub async fn rejected_signatures(&self) -> usize {
+        self.exchange.lock().await.rejected_signatures
+    }
+
+    /// Returns the txid of every order still open.
+    pub async fn open_orders(&self) -> Vec<String> {
+        self.exchange.lock().await.open.keys().cloned().collect()
+    }
+
+    /// Sends the next book update with a wrong checksum.
+    pub async fn corrupt_next_checksum(&self) {
+        self.exchange.lock().await.corrupt_checksums += 1;
+    }
+
+    /// Changes the price quoted by the ticker from its next update.
+    pub async fn set_price(&self, price: f64) {
+        self.exchange.lock().await.price = price;
+    }
+
+    /// Moves the book through the next post-only order, so it's rejected for taking liquidity.
+    pub async fn cross_next_order(&self) {
+        self.exchange.lock().await.crossing_orders += 1;
+    }
+
+    /// Changes the exchange status and announces it on every connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - One of online, maintenance, cancel_only or post_only.
+    pub async fn set_status(&self, status: &str) {
+        let mut exchange = self.exchange.lock().await;
+        exchange.status = status.to_string();
+        let message = system_status(status).to_string();
+        exchange
+            .clients
+            .retain(|client| client.send(message.clone()).is_ok());
+    }
+
+    /// Returns how many times the book was subscribed to.
+    pub async fn book_subscriptions(&self) -> usize {
+        self.exchange.lock().await.book_subscriptions
+    }
+
+    /// Fills an open order completely at its limit price.
+    pub async fn fill(&self, txid: &str) {
+        let mut exchange = self.exchange.lock().await;
+        let order = exchange.open.remove(txid).expect("Unknown order");
+        let price: f64 = order["price"].as_str().unwrap().parse().unwrap();
+        let volume: f64 = order["volume"].as_str().unwrap().parse().unwrap();
+        let cost = price * volume;
+        exchange.push_orders(json!({
+            txid: {
+                "status": "closed",
+                "vol_exec": volume.to_string(),
+                "cost": cost.to_string(),
+                "fee": (cost * 0.0025).to_string(),
+                "avg_price": price.to_string(),
+            }
+        }));
+    }
+}
+
+/// Polls `condition` until it holds, panicking if it doesn't within a few seconds.
+pub async fn wait_until<F, Fut>(what: &str, mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    for _ in 0..100 {
+        if condition().await {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("Timed out waiting for {}", what);
+}
+
+async fn ohlc(
+    State(exchange): State<Arc<Mutex<Exchange>>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let price = exchange.lock().await.price.to_string();
+    let now = now() as i64 / 60 * 60;
+    let rows: Vec<Value> = (0..30)
+        .map(|i| {
+            json!([
+                now - (30 - i) * 60,
+                price,
+                price,
+                price,
+                price,
+                price,
+                "1.0",
+                1
+            ])
+        })
+        .collect();
+    Json(json!({ "error": [], "result": { query["pair"].clone(): rows, "last": now } }))
+}
+
+async fn asset_pairs(Query(query): Query<HashMap<String, String>>) -> Json<Value> {
+    Json(json!({ "error": [], "result": { query["pair"].clone(): { "ordermin": "0.002" } } }))
+}
+
+async fn spread(
+    State(exchange): State<Arc<Mutex<Exchange>>>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let price = exchange.lock().await.price;
+    let now = now() as i64;
+    let rows: Vec<Value> = (0..30)
+        .map(|i| {
+            json!([
+                now - (30 - i) * 10,
+                (price * 0.9999).to_string(),
+                (price * 1.0001).to_string()
+            ])
+        })
+        .collect();
+    Json(json!({ "error": [], "result": { query["pair"].clone(): rows, "last": now } }))
+}
+
+/// Verifies the key, signature and nonce before answering a private endpoint.
+async fn private(
+    State(exchange): State<Arc<Mutex<Exchange>>>,
+    Path(method): Path<String>,
+    headers: HeaderMap,
+    body: String,
+) -> Json<Value> {
+    let mut exchange = exchange.lock().await;
+    let path = format!("/0/private/{}", method);
+    let params: HashMap<String, String> = serde_urlencoded::from_str(&body).unwrap_or_default();
+    let nonce = params.get("nonce").cloned().unwrap_or_default();
+
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    if header("API-Key") != KEY {
+        return error("EAPI:Invalid key");
+    }
+    if header("API-Sign") != sign(&exchange.secret, &path, &nonce, &body) {
+        exchange.rejected_signatures += 1;
+        return error("EAPI:Invalid signature");
+    }
+    let nonce = nonce.parse::<u64>().unwrap_or(0);
+    if nonce <= exchange.last_nonce {
+        return error("EAPI:Invalid nonce");
+    }
+    exchange.last_nonce = nonce;
+
+    match method.as_str() {
+        "Balance" => {
+            let balances: HashMap<&String, String> = exchange
+                .balances
+                .iter()
+                .map(|(asset, amount)| (asset, amount.to_string()))
+                .collect();
+            Json(json!({ "error": [], "result": balances }))
+        }
+        "GetWebSocketsToken" => {
+            Json(json!({ "error": [], "result": { "token": TOKEN, "expires": 900 } }))
+        }
+        "TradeVolume" => {
+            let pair = params.get("pair").cloned().unwrap_or_default();
+            Json(json!({
+                "error": [],
+                "result": {
+                    "currency": "ZUSD",
+                    "volume": "0.0000",
+                    "fees": { pair.clone(): { "fee": "0.4000" } },
+                    "fees_maker": { pair: { "fee": "0.2500" } },
+                }
+            }))
+        }
+        "OpenOrders" => {
+            let open: HashMap<&String, Value> = exchange
+                .open
+                .iter()
+                .map(|(txid, order)| {
+                    let order = json!({
+                        "status": "open",
+                        "vol": order["volume"],
+                        "vol_exec": "0.00000000",
+                        "descr": {
+                            "pair": order["pair"],
+                            "type": order["type"],
+                            "ordertype": order["ordertype"],
+                            "price": order["price"],
+                        },
+                    });
+                    (txid, order)
+                })
+                .collect();
+            Json(json!({ "error": [], "result": { "open": open } }))
+        }
+        "CancelAll" => {
+            let txids: Vec<String> = exchange.open.drain().map(|(txid, _)| txid).collect();
+            for txid in txids.iter() {
+                exchange.cancels.push(txid.clone());
+                exchange.push_orders(json!({ txid: { "status": "canceled" } }));
+            }
+            Json(json!({ "error": [], "result": { "count": txids.len() } }))
+        }
+        _ => error("EGeneral:Unknown method"),
+    }
+}
+
+fn error(message: &str) -> Json<Value> {
+    Json(json!({ "error": [message] }))
+}
+
+/// Returns Kraken's API-Sign for a request.
+pub fn sign(secret: &[u8], path: &str, nonce: &str, post_data: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(nonce.as_bytes());
+    hasher.update(post_data.as_bytes());
+    let mut mac = Hmac::<Sha512>::new_from_slice(secret).unwrap();
+    mac.update(path.as_bytes());
+    mac.update(&hasher.finalize());
+    general_purpose::STANDARD.encode(mac.finalize().into_bytes())
+}
+
+async fn accept(listener: TcpListener, exchange: Arc<Mutex<Exchange>>, private: bool) {
+    loop {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio::spawn(connection(stream, exchange.clone(), private));
+    }
+}
+
+async fn connection(stream: TcpStream, exchange: Arc<Mutex<Exchange>>, private: bool) {
+    let socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+    let (mut sink, mut stream) = socket.split();
+    let (outbox, mut inbox) = mpsc::unbounded_channel::<String>();
+    tokio::spawn(async move {
+        while let Some(message) = inbox.recv().await {
+            if sink.send(Message::Text(message)).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    {
+        let mut exchange = exchange.lock().await;
+        outbox
+            .send(system_status(&exchange.status).to_string())
+            .unwrap();
+        exchange.clients.push(outbox.clone());
+        if private {
+            exchange.private_clients.push(outbox.clone());
+        }
+    }
+
+    while let Some(Ok(message)) = stream.next().await {
+        let request: Value = match serde_json::from_str(&message.to_string()) {
+            Ok(request) => request,
+            Err(_) => continue,
+        };
+        match request["event"].as_str().unwrap_or_default() {
+            "subscribe" => subscribe(&request, &exchange, &outbox).await,
+            "addOrder" => add_order(request, &exchange, &outbox).await,
+            "cancelOrder" => cancel_order(&request, &exchange, &outbox).await,
+            _ => {}
+        }
+    }
+}
+
+fn system_status(status: &str) -> Value {
+    json!({
+        "connectionID": 1,
+        "event": "systemStatus",
+        "status": status,
+        "version": "1.9.0"
+    })
+}
+
+async fn subscribe(request: &Value, exchange: &Arc<Mutex<Exchange>>, outbox: &Outbox) {
+    let name = request["subscription"]["name"].as_str().unwrap_or_default();
+    let pair = request["pair"][0].as_str().unwrap_or_default().to_string();
+    if name == "openOrders" && request["subscription"]["token"] != TOKEN {
+        let status = json!({
+            "event": "subscriptionStatus",
+            "status": "error",
+            "errorMessage": "EGeneral:Invalid arguments:token",
+        });
+        outbox.send(status.to_string()).unwrap();
+        return;
+    }
+
+    let mut status = json!({
+        "channelName": name,
+        "event": "subscriptionStatus",
+        "status": "subscribed",
+        "subscription": { "name": name },
+    });
+    if !pair.is_empty() {
+        status["pair"] = json!(pair);
+    }
+    outbox.send(status.to_string()).unwrap();
+
+    match name {
+        "ticker" => {
+            let exchange = exchange.clone();
+            let outbox = outbox.clone();
+            tokio::spawn(async move {
+                loop {
+                    let price = exchange.lock().await.price;
+                    let bid = format!("{:.2}", price * 0.9999);
+                    let ask = format!("{:.2}", price * 1.0001);
+                    let ticker = json!([
+                        1,
+                        {
+                            "a": [ask, 1, "1.0"],
+                            "b": [bid, 1, "1.0"],
+                            "c": [format!("{:.2}", price), "0.1"],
+                            "v": ["100.0", "1000.0"],
+                            "p": [price.to_string(), price.to_string()],
+                            "t": [10, 100],
+                            "l": [bid, bid],
+                            "h": [ask, ask],
+                            "o": [price.to_string(), price.to_string()],
+                        },
+                        "ticker",
+                        pair
+                    ]);
+                    if outbox.send(ticker.to_string()).is_err() {
+                        break;
+                    }
+                    tokio::time::sleep(Duration::from_millis(TICKER_INTERVAL)).await;
+                }
+            });
+        }
+        "ohlc" => {
+            let price = format!("{:.2}", exchange.lock().await.price);
+            let now = now();
+            let end = (now as i64 / 60 + 1) * 60;
+            let bar = json!([
+                2,
+                [
+                    format!("{:.6}", now),
+                    format!("{:.6}", end),
+                    price,
+                    price,
+                    price,
+                    price,
+                    price,
+                    "1.0",
+                    1
+                ],
+                "ohlc-1",
+                pair
+            ]);
+            outbox.send(bar.to_string()).unwrap();
+        }
+        "book" => {
+            let mut exchange = exchange.lock().await;
+            exchange.book_subscriptions += 1;
+            let levels = |side: f64| -> Vec<(String, String)> {
+                (1..=10)
+                    .map(|i| {
+                        let price = exchange.price * (1.0 + side * 0.0001 * i as f64);
+                        (format!("{:.2}", price), "1.00000000".to_string())
+                    })
+                    .collect()
+            };
+            let (mut asks, bids) = (levels(1.0), levels(-1.0));
+            let entries = |levels: &[(String, String)]| -> Vec<Value> {
+                levels
+                    .iter()
+                    .map(|(price, volume)| json!([price, volume, format!("{:.6}", now())]))
+                    .collect()
+            };
+            let snapshot =
+                json!([3, { "as": entries(&asks), "bs": entries(&bids) }, "book-10", pair]);
+            outbox.send(snapshot.to_string()).unwrap();
+
+            asks[0].1 = "2.00000000".to_string();
+            let mut checksum = book_checksum(&asks, &bids);
+            if exchange.corrupt_checksums > 0 {
+                exchange.corrupt_checksums -= 1;
+                checksum = checksum.wrapping_add(1);
+            }
+            let update = json!([
+                3,
+                { "a": entries(&asks[..1]), "c": checksum.to_string() },
+                "book-10",
+                pair
+            ]);
+            outbox.send(update.to_string()).unwrap();
+        }
+        "openOrders" => {
+            let mut exchange = exchange.lock().await;
+            exchange.sequence += 1;
+            let snapshot = json!([[], "openOrders", { "sequence": exchange.sequence }]);
+            outbox.send(snapshot.to_string()).unwrap();
+        }
+        _ => {}
+    }
+}
+
+async fn add_order(request: Value, exchange: &Arc<Mutex<Exchange>>, outbox: &Outbox) {
+    let mut exchange = exchange.lock().await;
+    if request["token"] != TOKEN {
+        let status = json!({
+            "event": "addOrderStatus",
+            "status": "error",
+            "errorMessage": "EGeneral:Invalid arguments:token",
+        });
+        outbox.send(status.to_string()).unwrap();
+        return;
+    }
+
+    exchange.orders.push(request.clone());
+    let crossing = request["oflags"] == "post" && exchange.crossing_orders > 0;
+    let error = match exchange.status.as_str() {
+        "maintenance" | "cancel_only" => Some("EService:Unavailable"),
+        "post_only" if request["oflags"] != "post" => Some("EOrder:Post only order required"),
+        _ if crossing => {
+            exchange.crossing_orders -= 1;
+            Some("EOrder:Post only order")
+        }
+        _ => None,
+    };
+    if let Some(error) = error {
+        let status = json!({
+            "event": "addOrderStatus",
+            "status": "error",
+            "errorMessage": error,
+        });
+        outbox.send(status.to_string()).unwrap();
+        return;
+    }
+
+    let txid = format!("OMOCK{:02}-AAAAA-BBBBBB", exchange.next_txid);
+    exchange.next_txid += 1;
+    exchange.open.insert(txid.clone(), request.clone());
+    let side = request["type"].as_str().unwrap_or_default();
+    let descr = format!(
+        "{} {} {} @ limit {}",
+        side,
+        request["volume"].as_str().unwrap_or_default(),
+        request["pair"].as_str().unwrap_or_default(),
+        request["price"].as_str().unwrap_or_default()
+    );
+    let status = json!({
+        "event": "addOrderStatus",
+        "status": "ok",
+        "txid": txid,
+        "descr": descr,
+    });
+    outbox.send(status.to_string()).unwrap();
+    exchange.push_orders(json!({
+        txid: {
+            "status": "pending",
+            "descr": {
+                "pair": request["pair"],
+                "type": side,
+                "ordertype": "limit",
+                "price": request["price"],
+                "price2": "0.00000",
+                "order": descr,
+            },
+            "vol": request["volume"],
+            "vol_exec": "0.00000000",
+            "cost": "0.00000",
+            "fee": "0.00000",
+            "avg_price": "0.00000",
+        }
+    }));
+}
+
+async fn cancel_order(request: &Value, exchange: &Arc<Mutex<Exchange>>, outbox: &Outbox) {
+    let mut exchange = exchange.lock().await;
+    for txid in request["txid"].as_array().cloned().unwrap_or_default() {
+        let txid = txid.as_str().unwrap_or_default().to_string();
+        let status = if exchange.open.remove(&txid).is_some() {
+            json!({ "event": "cancelOrderStatus", "status": "ok" })
+        } else {
+            json!({
+                "event": "cancelOrderStatus",
+                "status": "error",
+                "errorMessage": "EOrder:Unknown order",
+            })
+        };
+        outbox.send(status.to_string()).unwrap();
+        exchange.cancels.push(txid.clone());
+        exchange.push_orders(json!({ txid: { "status": "canceled" } }));
+    }
+}
+
+/// Kraken's book checksum, a CRC32 over the asks then bids with the decimal points and
+/// leading zeros removed.
+fn book_checksum(asks: &[(String, String)], bids: &[(String, String)]) -> u32 {
+    let strip = |s: &str| s.replace('.', "").trim_start_matches('0').to_string();
+    let payload: String = asks
+        .iter()
+        .chain(bids.iter())
+        .map(|(price, volume)| strip(price) + &strip(volume))
+        .collect();
+    crc32fast::hash(payload.as_bytes())
+}
+
+fn now() -> f64 {
+    std::time::UNIX_EPOCH.elapsed().unwrap().as_secs_f64()
+}
@@ -0,0 +1,3 @@
+// Each test binary only uses part of the mock
+#[allow(dead_code)]
+pub mod mock_kraken;
@@ -0,0 +1,122 @@
+use rebalancer::config::VolatilityConfig;
+use rebalancer::volatility::{Bar, Volatility};
+
+const SIGMA: f64 = 0.001; // Per minute
+
+/// Feeds prices alternating up and down by the move a random walk with SIGMA per minute makes
+/// on average in `interval` seconds.
+fn feed_prices(volatility: &mut Volatility, interval: f64, count: usize) {
+    let step = SIGMA * (interval / 60.0).sqrt();
+    let mut price = 100.0;
+    for i in 0..count {
+        volatility.record_price(i as f64 * interval, price);
+        price *= if i % 2 == 0 {
+            step.exp()
+        } else {
+            (-step).exp()
+        };
+    }
+}
+
+/// Feeds flat 1 minute bars whose range Parkinson maps to SIGMA.
+fn feed_bars(volatility: &mut Volatility, count: usize) {
+    let range = SIGMA * (4.0 * 2.0_f64.ln()).sqrt();
+    for i in 0..count {
+        volatility.record_bar(Bar {
+            time: 60.0 * (i + 1) as f64,
+            open: 100.0,
+            high: 100.0 * (range / 2.0).exp(),
+            low: 100.0 * (-range / 2.0).exp(),
+            close: 100.0,
+        });
+    }
+}
+
+fn assert_close(estimate: Option<f64>, expected: f64) {
+    let estimate = estimate.expect("No estimate");
+    assert!(
+        (estimate / expected - 1.0).abs() < 1e-6,
+        "{} != {}",
+        estimate,
+        expected
+    );
+}
+
+#[test]
+fn price_estimators_scale_to_one_minute() {
+    for config in [
+        VolatilityConfig::Stdev { window: 50 },
+        VolatilityConfig::Ewma {
+            halflife: 10.0,
+            min_samples: 10,
+        },
+    ] {
+        // 10 second samples, as recorded live, and 1 minute closes, as from history
+        for interval in [10.0, 60.0] {
+            let mut volatility = Volatility::new(&config);
+            feed_prices(&mut volatility, interval, 101);
+            assert_close(volatility.estimate(), SIGMA);
+        }
+    }
+}
+
+#[test]
+fn warm_start_closes_continue_into_live_samples() {
+    let mut volatility = Volatility::new(&VolatilityConfig::Ewma {
+        halflife: 10.0,
+        min_samples: 10,
+    });
+    let (mut time, mut price) = (0.0, 100.0);
+    for (interval, count) in [(60.0, 30), (10.0, 30)] {
+        let step: f64 = SIGMA * (interval / 60.0_f64).sqrt();
+        for i in 0..count {
+            time += interval;
+            price *= if i % 2 == 0 {
+                step.exp()
+            } else {
+                (-step).exp()
+            };
+            volatility.record_price(time, price);
+        }
+    }
+    assert_close(volatility.estimate(), SIGMA);
+}
+
+#[test]
+fn bar_estimators() {
+    let mut parkinson = Volatility::new(&VolatilityConfig::Parkinson { window: 10 });
+    feed_bars(&mut parkinson, 9);
+    assert!(parkinson.estimate().is_none());
+    feed_bars(&mut parkinson, 1);
+    assert_close(parkinson.estimate(), SIGMA);
+
+    // With no drift Garman-Klass is sqrt(0.5) times the range
+    let mut garman_klass = Volatility::new(&VolatilityConfig::GarmanKlass { window: 10 });
+    feed_bars(&mut garman_klass, 10);
+    let range = SIGMA * (4.0 * 2.0_f64.ln()).sqrt();
+    assert_close(garman_klass.estimate(), range * 0.5_f64.sqrt());
+}
+
+#[test]
+fn blends_estimates_on_the_same_horizon() {
+    let mut volatility = Volatility::new(&VolatilityConfig::Blend {
+        short: Box::new(VolatilityConfig::Ewma {
+            halflife: 30.0,
+            min_samples: 10,
+        }),
+        long: Box::new(VolatilityConfig::Parkinson { window: 10 }),
+        weight: 0.3,
+    });
+    feed_prices(&mut volatility, 10.0, 61);
+    feed_bars(&mut volatility, 10);
+    assert_close(volatility.estimate(), SIGMA);
+}
+
+#[test]
+fn ignores_repeated_timestamps() {
+    let mut volatility = Volatility::new(&VolatilityConfig::Stdev { window: 10 });
+    volatility.record_price(0.0, 100.0);
+    volatility.record_price(0.0, 101.0);
+    volatility.record_price(0.0, 100.0);
+    assert!(volatility.estimate().is_none());
+}